@@ -7,7 +7,8 @@ export default function GetLatestBlock() {
     return (
       <>
         blockheight : {block.getIndex()} , blockhash :{" "}
-        {base58.encode(block.getBlockhash_asU8())}
+        {base58.encode(block.getBlockhash_asU8())} , time :{" "}
+        {new Date(block.getTimestamp() * 1000).toLocaleString()}
       </>
     );
   } else {
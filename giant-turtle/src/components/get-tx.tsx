@@ -1,26 +1,51 @@
 import useRpc from "@giant-turtle/hooks/rpc";
-import { GetHistoryRequest, Transaction } from "@giant-turtle/proto/pb_pb";
+import {
+  GetTransactionRequest,
+  GetTransactionResponse,
+  State,
+} from "@giant-turtle/proto/pb_pb";
 import { base58 } from "@scure/base";
 import { useState } from "react";
 import { TransactionCard } from "./tx-card";
 
+function displayStatus(status: GetTransactionResponse | undefined) {
+  if (!status) {
+    return <></>;
+  }
+  switch (status.getState()) {
+    case State.SUCCESS:
+      return (
+        <li>
+          status : confirmed in block {status.getBlockIndex()} (
+          {base58.encode(status.getBlockhash_asU8())}),{" "}
+          {status.getConfirmations()} confirmations
+        </li>
+      );
+    case State.PENDING:
+      return <li>status : pending</li>;
+    default:
+      return <li>status : failed, {status.getReason()}</li>;
+  }
+}
+
 export default function GetTx() {
   const rpc = useRpc();
   const [sign, setSign] = useState<string>("");
-  const [tx, setTx] = useState<Transaction | undefined>(undefined);
+  const [status, setStatus] = useState<GetTransactionResponse | undefined>(
+    undefined
+  );
 
   const handleChange = (event: any) => {
     setSign(event.target.value);
   };
   const handleSubmit = (event: any) => {
-    rpc.getHistory(new GetHistoryRequest(), (e, v) => {
-      if (v && sign.length !== 0) {
-        const transac = v.getTransactionsList().find((tx) => {
-          return base58.encode(tx.getSignature_asU8()) === sign;
-        });
-        setTx(transac);
-      }
-    });
+    if (sign.length !== 0) {
+      const req = new GetTransactionRequest();
+      req.setSignature(base58.decode(sign));
+      rpc.getTransaction(req, (e, v) => {
+        setStatus(v ?? undefined);
+      });
+    }
 
     event.preventDefault();
   };
@@ -39,7 +64,8 @@ export default function GetTx() {
         </label>
         <input type="submit" value="Fetch" />
       </form>
-      Result : {TransactionCard(tx)}
+      Result : {TransactionCard(status?.getTx())}
+      {displayStatus(status)}
     </>
   );
 }
@@ -4,8 +4,14 @@ import { useState } from "react";
 import { base58 } from "@scure/base";
 import useRpc from "@giant-turtle/hooks/rpc";
 import { SendTransactionRequest } from "@giant-turtle/proto/pb_pb";
-import { u64ToArray } from "@giant-turtle/serde/utils";
+import {
+  getSignedPayload,
+  u64ToArray,
+  VALIDITY_WINDOW,
+} from "@giant-turtle/serde/utils";
 import useLastestBlock from "@giant-turtle/hooks/get-lastest-block";
+import useNonce from "@giant-turtle/hooks/nonce";
+import useChainId from "@giant-turtle/hooks/chain-id";
 
 export default function MintTx() {
   const [amount, setAmount] = useState<number>(0);
@@ -14,6 +20,8 @@ export default function MintTx() {
   const wallet = useWallet();
   const rpc = useRpc();
   const block = useLastestBlock();
+  const fetchNonce = useNonce();
+  const fetchChainId = useChainId();
 
   const handleChange = (event: any) => {
     setAmount(event.target.value);
@@ -24,19 +32,24 @@ export default function MintTx() {
     if (wallet.signMessage && wallet.publicKey && block) {
       const tx = new Transaction();
 
-      const blockheight = block.getIndex() + 1;
+      const validFrom = block.getIndex() + 1;
+      const validUntil = validFrom + VALIDITY_WINDOW;
       const gas = 5;
+      const nonce = await fetchNonce(wallet.publicKey.toBytes());
       const msg = [0].concat(u64ToArray(amount));
-      tx.setBlockheight(blockheight);
+      tx.setValidFrom(validFrom);
+      tx.setValidUntil(validUntil);
       tx.setGas(gas);
+      tx.setNonce(nonce);
       tx.setMsg(new Uint8Array(msg));
       tx.setPayer(wallet.publicKey.toBytes());
-      const toSign = new TextEncoder().encode(
-        base58.encode(
-          new Uint8Array(
-            u64ToArray(blockheight).concat(u64ToArray(gas)).concat(msg)
-          )
-        )
+      const toSign = getSignedPayload(
+        await fetchChainId(),
+        u64ToArray(validFrom)
+          .concat(u64ToArray(validUntil))
+          .concat(u64ToArray(gas))
+          .concat(u64ToArray(nonce))
+          .concat(msg)
       );
       const sign = await wallet.signMessage(toSign);
 
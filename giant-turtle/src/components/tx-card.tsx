@@ -21,7 +21,9 @@ export function TransactionCard(tx: Transaction | undefined) {
   if (tx) {
     return (
       <>
-        <li>blockheight : {tx.getBlockheight()}</li>
+        <li>
+          valid from : {tx.getValidFrom()} to {tx.getValidUntil()}
+        </li>
         <li>signature : {base58.encode(tx.getSignature_asU8())}</li>
         <li>
           msg :{" "}
@@ -47,6 +49,12 @@ function displayTransactionMessage(msg: TransactionMessage) {
         Minted : {msg.mint.amount} {SeaShell(20, 20)}
       </>
     );
+  } else if (msg.coinbase) {
+    return (
+      <>
+        Block reward : {msg.coinbase.amount} {SeaShell(20, 20)}
+      </>
+    );
   } else if (msg.transfer) {
     return (
       <>
@@ -3,26 +3,61 @@ import { GetHistoryRequest, Transaction } from "@giant-turtle/proto/pb_pb";
 import { useEffect, useState } from "react";
 import { TransactionResumeCard } from "./tx-card";
 
+// Keeps the order of `first`, then the ones of `second` it does not hold.
+function merge(first: Transaction[], second: Transaction[]) {
+  const seen = new Set(first.map((tx) => tx.getSignature_asB64()));
+  return first.concat(
+    second.filter((tx) => !seen.has(tx.getSignature_asB64()))
+  );
+}
+
 export default function TxHistory() {
-  const [history, setHistory] = useState<Transaction[]>([]);
+  // Latest transactions, refreshed from the first page.
+  const [latest, setLatest] = useState<Transaction[]>([]);
+  // Pages loaded on demand, older than `latest`.
+  const [older, setOlder] = useState<Transaction[]>([]);
+  const [firstCursor, setFirstCursor] = useState<Uint8Array>(
+    new Uint8Array()
+  );
+  // Cursor of the last page loaded on demand, empty once the history is exhausted.
+  const [cursor, setCursor] = useState<Uint8Array | undefined>(undefined);
   const rpc = useRpc();
   useEffect(() => {
     const call = setInterval(
       () =>
         rpc.getHistory(new GetHistoryRequest(), (e, v) => {
           if (v) {
-            setHistory(v.getTransactionsList());
+            // Merged rather than replaced, so that transactions pushed out of the
+            // first page by new ones stay shown above the older pages.
+            setLatest((previous) => merge(v.getTransactionsList(), previous));
+            setFirstCursor(v.getNextCursor_asU8());
           }
         }),
       5000
     );
     return () => clearInterval(call);
   });
+
+  const nextCursor = cursor ?? firstCursor;
+  const loadMore = () => {
+    const req = new GetHistoryRequest();
+    req.setCursor(nextCursor);
+    rpc.getHistory(req, (e, v) => {
+      if (v) {
+        setOlder((previous) => previous.concat(v.getTransactionsList()));
+        setCursor(v.getNextCursor_asU8());
+      }
+    });
+  };
+
   return (
     <>
-      {history.map((tx, i) => (
+      {merge(latest, older).map((tx, i) => (
         <li key={i}>{TransactionResumeCard(tx)}</li>
       ))}
+      {nextCursor.length > 0 && (
+        <button onClick={loadMore}>Load more</button>
+      )}
     </>
   );
 }
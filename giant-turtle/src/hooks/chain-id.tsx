@@ -0,0 +1,20 @@
+import { HandshakeRequest } from "@giant-turtle/proto/pb_pb";
+import { useCallback } from "react";
+import useRpc from "./rpc";
+
+export default function useChainId() {
+  const rpc = useRpc();
+  return useCallback(
+    () =>
+      new Promise<string>((resolve, reject) => {
+        rpc.handshake(new HandshakeRequest(), (e, v) => {
+          if (v) {
+            resolve(v.getChainId());
+          } else {
+            reject(e);
+          }
+        });
+      }),
+    [rpc]
+  );
+}
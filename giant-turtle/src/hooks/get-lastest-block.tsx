@@ -1,4 +1,8 @@
-import { Block, GetLatestBlockRequest } from "@giant-turtle/proto/pb_pb";
+import {
+  Block,
+  GetLatestBlockRequest,
+  SubscribeBlocksRequest,
+} from "@giant-turtle/proto/pb_pb";
 import { useState, useEffect, useMemo } from "react";
 import useRpc from "./rpc";
 
@@ -6,16 +10,18 @@ export default function useLastestBlock() {
   const [block, setBlock] = useState<Block | undefined>(undefined);
   const rpc = useRpc();
   useEffect(() => {
-    const call = setInterval(
-      () =>
-        rpc.getLatestBlock(new GetLatestBlockRequest(), (e, v) => {
-          if (v) {
-            setBlock(v.getBlock());
-          }
-        }),
-      5000
-    );
-    return () => clearInterval(call);
+    rpc.getLatestBlock(new GetLatestBlockRequest(), (e, v) => {
+      if (v) {
+        setBlock(v.getBlock());
+      }
+    });
+    const stream = rpc.subscribeBlocks(new SubscribeBlocksRequest());
+    stream.on("data", (event) => {
+      if (event.getConnected()) {
+        setBlock(event.getBlock());
+      }
+    });
+    return () => stream.cancel();
   }, [rpc]);
 
   return useMemo(() => block, [block]);
@@ -0,0 +1,22 @@
+import { GetBalanceRequest } from "@giant-turtle/proto/pb_pb";
+import { useCallback } from "react";
+import useRpc from "./rpc";
+
+export default function useNonce() {
+  const rpc = useRpc();
+  return useCallback(
+    (address: Uint8Array) =>
+      new Promise<number>((resolve, reject) => {
+        const req = new GetBalanceRequest();
+        req.setAddress(address);
+        rpc.getBalance(req, (e, v) => {
+          if (v) {
+            resolve(v.getNonce());
+          } else {
+            reject(e);
+          }
+        });
+      }),
+    [rpc]
+  );
+}
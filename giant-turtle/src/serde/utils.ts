@@ -1,3 +1,8 @@
+import { base58 } from "@scure/base";
+
+// Number of blocks a signed transaction stays includable for.
+export const VALIDITY_WINDOW = 50;
+
 export function u64ToArray(n: number) {
   return [0, 0, 0, 0, 0, 0, 0, 0].map((val, i) => {
     return (n / 2 ** (8 * i)) & 0xff;
@@ -12,8 +17,19 @@ export function getU64fromArray(data: Uint8Array) {
   return res;
 }
 
+// What the payer signs : the transaction content bound to the network chain id.
+export function getSignedPayload(chainId: string, content: number[]) {
+  const id = Array.from(new TextEncoder().encode(chainId));
+  return new TextEncoder().encode(
+    base58.encode(
+      new Uint8Array(u64ToArray(id.length).concat(id).concat(content))
+    )
+  );
+}
+
 export type TransactionMessage = {
   mint?: { amount: number };
+  coinbase?: { amount: number };
   transfer?: { to: Uint8Array; amount: number };
 };
 
@@ -22,6 +38,9 @@ export function decodeTransactionMessage(data: Uint8Array): TransactionMessage {
   if (index === 0) {
     const amount = getU64fromArray(data.slice(1));
     return { mint: { amount: amount } };
+  } else if (index === 2) {
+    const amount = getU64fromArray(data.slice(1));
+    return { coinbase: { amount: amount } };
   } else {
     const pk = data.slice(1, 33);
     const amount = getU64fromArray(data.slice(33));
@@ -0,0 +1,30 @@
+use ed25519_dalek::{Keypair, SecretKey};
+
+use crate::err::GulfStreamError;
+
+/// Keypair of a base58 secret key, as given to the node and the client.
+pub fn keypair_from_str(secret: &str) -> Result<Keypair, GulfStreamError> {
+    let bytes = bs58::decode(secret)
+        .into_vec()
+        .map_err(|_| GulfStreamError::SerDeError("Keypair".into()))?;
+    let secret =
+        SecretKey::from_bytes(&bytes).map_err(|_| GulfStreamError::SerDeError("Keypair".into()))?;
+    let public = (&secret).into();
+    Ok(Keypair { secret, public })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reads_base58_secret() {
+        let secret = bs58::encode([7; 32]).into_string();
+        let keypair = keypair_from_str(&secret).unwrap();
+
+        assert_eq!(keypair.secret.as_bytes(), &[7; 32]);
+        assert_eq!(keypair.public, (&keypair.secret).into());
+        assert!(keypair_from_str("not base58 0OIl").is_err());
+        assert!(keypair_from_str(&bs58::encode([7; 16]).into_string()).is_err());
+    }
+}
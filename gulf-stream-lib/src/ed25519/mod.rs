@@ -1,2 +1,3 @@
+pub mod keypair;
 pub mod publickey;
 pub mod signature;
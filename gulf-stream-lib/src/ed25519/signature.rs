@@ -3,7 +3,9 @@ use crate::{
     state::transaction::{Transaction, TransactionMessage},
     utils::serde::{BytesDeserialize, BytesSerialize},
 };
+use core::hash::Hash;
 use ed25519_dalek::{Keypair, Signer};
+use std::{cmp::Ordering, hash::Hasher};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Signature(pub ed25519_dalek::Signature);
@@ -22,23 +24,45 @@ impl Signature {
 
     pub fn sign_payload(
         signer: &Keypair,
-        blockheight: u64,
+        chain_id: &str,
+        valid_from: u64,
+        valid_until: u64,
         gas: u64,
+        nonce: u64,
         msg: TransactionMessage,
     ) -> Transaction {
-        let mut message = vec![];
-        message.extend(blockheight.serialize());
-        message.extend(gas.serialize());
-        message.extend(msg.serialize());
-        let encoded = bs58::encode(message).into_vec();
-        let signature = signer.try_sign(encoded.as_slice()).unwrap();
-        Transaction {
-            blockheight,
+        let mut tx = Transaction {
+            valid_from,
+            valid_until,
             payer: signer.public.into(),
             msg,
-            signature: signature.into(),
+            signature: Default::default(),
             gas,
-        }
+            nonce,
+        };
+        let encoded = tx.get_signed_payload(chain_id);
+        tx.signature = signer.try_sign(encoded.as_slice()).unwrap().into();
+        tx
+    }
+}
+
+impl Eq for Signature {}
+
+impl Hash for Signature {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bytes().hash(state);
+    }
+}
+
+impl PartialOrd for Signature {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Signature {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.to_bytes().cmp(&other.0.to_bytes())
     }
 }
 
@@ -63,6 +87,9 @@ impl BytesSerialize for Signature {
 
 impl BytesDeserialize for Signature {
     fn deserialize(buf: &mut &[u8]) -> Result<Self, GulfStreamError> {
+        if buf.len() < ed25519_dalek::SIGNATURE_LENGTH {
+            return Err(GulfStreamError::SerDeError("Signature".into()));
+        }
         let data = &buf[..ed25519_dalek::SIGNATURE_LENGTH];
         *buf = &buf[ed25519_dalek::SIGNATURE_LENGTH..];
         Ok(Self(ed25519_dalek::Signature::from_bytes(data).map_err(
@@ -18,12 +18,60 @@ pub enum GulfStreamError {
     WrongIndex,
     #[error("Unknown block")]
     BlockNotFound,
+    #[error("Unknown transaction")]
+    TxNotFound,
     #[error("Lock error")]
     TryLockError,
     #[error("Failed to find previous blockhash")]
     DidNotFindPreviousBlock,
+    #[error("Block already in the chain")]
+    BlockAlreadyKnown,
     #[error("This transaction is not valid")]
     TxIsNotValid,
+    #[error("Blockhash does not match the block content")]
+    InvalidBlockhash,
+    #[error("Block timestamp is not above the median time past")]
+    BlockTooOld,
+    #[error("Block timestamp is too far in the future")]
+    BlockTooFarInFuture,
+    #[error("Block difficulty does not follow the retargeting rule")]
+    WrongDifficulty,
+    #[error("Blockhash does not meet the difficulty target")]
+    InsufficientProofOfWork,
+    #[error("Transaction signature is not valid")]
+    InvalidTxSignature,
+    #[error("Transaction message is not valid")]
+    InvalidTxMessage,
+    #[error("Transaction validity window is malformed or too long")]
+    InvalidValidityWindow,
+    #[error("Block index is outside the transaction validity window")]
+    TxOutsideValidityWindow,
+    #[error("Transaction nonce does not follow the payer sequence")]
+    WrongTxNonce,
+    #[error("Transaction nonce already used by the payer")]
+    TxNonceAlreadyUsed,
+    #[error("Transaction already in the mempool")]
+    TxAlreadyInMempool,
+    #[error("Replacement transaction must pay more gas than the pending one")]
+    ReplacementFeeTooLow,
+    #[error("Payer has too many pending transactions")]
+    TooManyPendingTxs,
+    #[error("Mempool is full and the transaction gas is too low")]
+    MempoolFull,
+    #[error("Transaction spends more than the payer balance")]
+    Overspending,
+    #[error("Only the mint authorities can mint")]
+    UnauthorizedMint,
+    #[error("Coinbase is misplaced or claims more than the block reward")]
+    InvalidCoinbase,
+    #[error("Amount is out of range")]
+    AmountOverflow,
+    #[error("Genesis config is not valid : {0}")]
+    InvalidGenesis(String),
+    #[error("Peer is on another network")]
+    NetworkMismatch,
+    #[error("Database schema version {0} is newer than this node")]
+    SchemaTooNew(i64),
     #[error("Something went wrong : {0}")]
     Generic(String),
     #[error("SerDeError : {0}")]
@@ -48,6 +96,12 @@ impl From<sqlx::Error> for GulfStreamError {
     }
 }
 
+impl From<sled::Error> for GulfStreamError {
+    fn from(value: sled::Error) -> Self {
+        GulfStreamError::Generic(value.to_string())
+    }
+}
+
 impl From<ParseIntError> for GulfStreamError {
     fn from(value: ParseIntError) -> Self {
         GulfStreamError::Generic(value.to_string())
@@ -68,7 +122,11 @@ impl Into<String> for GulfStreamError {
 
 impl Into<Status> for GulfStreamError {
     fn into(self) -> Status {
-        Status::aborted(self)
+        match self {
+            // A distinct code, so that peers tell it apart from a failed call.
+            GulfStreamError::NetworkMismatch => Status::failed_precondition(self),
+            _ => Status::aborted(self),
+        }
     }
 }
 
@@ -80,4 +138,29 @@ impl GulfStreamError {
     pub fn map_to_status<T: Into<String>>(value: T) -> Status {
         GulfStreamError::map(value).into()
     }
+
+    /// Errors only a peer breaking the protocol makes us return, as opposed to errors that
+    /// follow from network delays or a diverging view of the chain.
+    pub fn is_misbehavior(&self) -> bool {
+        matches!(
+            self,
+            GulfStreamError::BlockIsNotValid
+                | GulfStreamError::WrongIndex
+                | GulfStreamError::WrongParentBlockhash
+                | GulfStreamError::InvalidBlockhash
+                | GulfStreamError::WrongDifficulty
+                | GulfStreamError::InsufficientProofOfWork
+                | GulfStreamError::InvalidTxSignature
+                | GulfStreamError::InvalidTxMessage
+                | GulfStreamError::InvalidValidityWindow
+                | GulfStreamError::TxOutsideValidityWindow
+                | GulfStreamError::WrongTxNonce
+                | GulfStreamError::Overspending
+                | GulfStreamError::UnauthorizedMint
+                | GulfStreamError::InvalidCoinbase
+                | GulfStreamError::AmountOverflow
+                | GulfStreamError::NetworkMismatch
+                | GulfStreamError::SerDeError(_)
+        )
+    }
 }
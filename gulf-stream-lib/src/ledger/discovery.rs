@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use crate::err::GulfStreamError;
+use crate::ledger::ledger::Ledger;
+use crate::pb::node_client::NodeClient;
+use crate::pb::{AnnouncePeerRequest, GetPeersRequest, HandshakeRequest};
+use crate::peer::peers::PeerBook;
+use crate::utils::time::unix_timestamp;
+
+use tonic::transport::{Channel, Endpoint};
+use tonic::Code;
+
+impl Ledger {
+    /// Seconds between two rounds of peer health checks.
+    pub const PEER_CHECK_INTERVAL: u64 = 30;
+
+    /// Periodically exchanges peers with every reachable peer. A peer that answers is
+    /// healthy, one that does not is backed off and eventually forgotten.
+    pub fn run_peers(self: Arc<Ledger>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                let nodes = self.peers.lock().await.get_active(unix_timestamp());
+                for endpoint in nodes.into_iter() {
+                    let uri = PeerBook::get_uri(&endpoint);
+                    match self.exchange_peers(&endpoint).await {
+                        Ok(peers) => {
+                            let mut peers_guard = self.peers.lock().await;
+                            peers_guard.report_success(&uri);
+                            peers
+                                .iter()
+                                .filter_map(|peer| PeerBook::parse_endpoint(peer).ok())
+                                .for_each(|peer| {
+                                    peers_guard.add(peer, unix_timestamp());
+                                });
+                        }
+                        Err(GulfStreamError::NetworkMismatch) => {
+                            println!("Peer {} is on another network", uri);
+                            self.connections.remove(&endpoint).await;
+                            self.peers.lock().await.ban(&uri, unix_timestamp());
+                        }
+                        Err(err) => {
+                            println!("Peer {} unreachable : {}", uri, err);
+                            self.connections.remove(&endpoint).await;
+                            self.peers
+                                .lock()
+                                .await
+                                .report_failure(&uri, unix_timestamp());
+                        }
+                    }
+                }
+                tokio::time::sleep(tokio::time::Duration::from_secs(Self::PEER_CHECK_INTERVAL))
+                    .await;
+            }
+        })
+    }
+
+    /// Checks the node behind `client` shares our chain id and genesis.
+    pub async fn handshake(&self, client: &mut NodeClient<Channel>) -> Result<(), GulfStreamError> {
+        let request = HandshakeRequest {
+            chain_id: self.get_chain_id().await,
+            genesis_hash: self.get_genesis_hash().await.into(),
+        };
+        let reply = client
+            .handshake(request.clone())
+            .await
+            .map_err(|err| match err.code() {
+                Code::FailedPrecondition => GulfStreamError::NetworkMismatch,
+                _ => GulfStreamError::map(err.message()),
+            })?
+            .into_inner();
+        if reply.chain_id != request.chain_id || reply.genesis_hash != request.genesis_hash {
+            return Err(GulfStreamError::NetworkMismatch);
+        }
+        Ok(())
+    }
+
+    /// Connects to `endpoint` and checks it is on our network.
+    pub async fn reach(&self, endpoint: &Endpoint) -> Result<(), GulfStreamError> {
+        let mut client = self.connections.get(endpoint).await?;
+        self.handshake(&mut client).await
+    }
+
+    /// Announces this node to `endpoint` and returns the peers it knows, provided both are
+    /// on the same network.
+    async fn exchange_peers(&self, endpoint: &Endpoint) -> Result<Vec<String>, GulfStreamError> {
+        let mut client = self.connections.get(endpoint).await?;
+        self.handshake(&mut client).await?;
+        let sender = self.get_sender();
+        if !sender.is_empty() {
+            client
+                .announce_peer(AnnouncePeerRequest {
+                    endpoint: sender,
+                    genesis_hash: self.get_genesis_hash().await.into(),
+                })
+                .await
+                .map_err(|err| GulfStreamError::map(err.message()))?;
+        }
+        Ok(client
+            .get_peers(GetPeersRequest {})
+            .await
+            .map_err(|err| GulfStreamError::map(err.message()))?
+            .into_inner()
+            .peers)
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+
+use crate::ed25519::publickey::PublicKey;
+use crate::ledger::ledger::Ledger;
+use crate::state::blockchain::ChainUpdate;
+use crate::state::link::Link;
+use crate::state::transaction::Transaction;
+
+/// What subscribers of the ledger are told about.
+#[derive(Clone)]
+pub enum LedgerEvent {
+    /// Blocks joined or left the canonical chain, `head` is the chain after the change.
+    ChainUpdated {
+        update: Arc<ChainUpdate>,
+        head: Arc<Link>,
+    },
+    /// A transaction entered the mem pool.
+    TransactionAdmitted(Box<Transaction>),
+}
+
+impl LedgerEvent {
+    /// Accounts whose balance or nonce on the canonical chain may have changed.
+    pub fn get_involved_pk(&self) -> Vec<PublicKey> {
+        match self {
+            LedgerEvent::ChainUpdated { update, .. } => update
+                .connected
+                .iter()
+                .chain(update.disconnected.iter())
+                .flat_map(|block| Transaction::get_involved_pk_from_txs(&block.transactions))
+                .collect(),
+            LedgerEvent::TransactionAdmitted(_) => vec![],
+        }
+    }
+}
+
+impl Ledger {
+    /// Events a slow subscriber can fall behind by before it misses some.
+    pub const EVENTS_CAPACITY: usize = 1024;
+
+    pub fn new_events() -> broadcast::Sender<LedgerEvent> {
+        broadcast::channel(Self::EVENTS_CAPACITY).0
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LedgerEvent> {
+        self.events.subscribe()
+    }
+
+    /// Nobody listening is not an error, the event is just dropped.
+    pub fn publish(&self, event: LedgerEvent) {
+        let _ = self.events.send(event);
+    }
+}
@@ -1,32 +1,57 @@
 use std::net::SocketAddr;
-use std::ops::Add;
 use std::sync::Arc;
 
 use crate::ed25519::publickey::PublicKey;
+use crate::ed25519::signature::Signature;
+use crate::err::GulfStreamError;
+use crate::ledger::events::LedgerEvent;
+use crate::ledger::sync::Synchronizer;
+use crate::mempool::mempool::Mempool;
+use crate::peer::metrics::DeliveryMetrics;
+use crate::peer::peers::PeerBook;
+use crate::peer::pool::ConnectionPool;
 use crate::rpc::rpc::GulfStreamRpc;
 use crate::state::block::Block;
-use crate::state::blockchain::Blockchain;
+use crate::state::blockchain::{Blockchain, ChainUpdate};
 use crate::state::blockhash::Blockhash;
+use crate::state::difficulty::next_difficulty;
+use crate::state::link::Link;
 use crate::state::transaction::Transaction;
-use crate::store::db::DbClient;
+use crate::store::history::{HistoryPage, HistoryQuery};
+use crate::store::store::Store;
+use crate::utils::seen::SeenSet;
+use crate::utils::time::unix_timestamp;
 use crate::{
-    pb::{node_server::NodeServer, SendBlockRequest},
+    pb::{node_server::NodeServer, SendBlockRequest, SendTransactionRequest},
     rpc::rpc::Broadcaster,
 };
 
-use tokio::sync::Mutex;
+use ed25519_dalek::Keypair;
+use tokio::sync::{broadcast, Mutex};
 use tonic::transport::{Endpoint, Server};
 
 use anyhow::Result;
 
 pub struct Ledger {
     pub state: Mutex<Blockchain>,
-    pub mem_pool: Mutex<Vec<Transaction>>,
-    pub other_nodes: Mutex<Vec<Endpoint>>,
-    pub db: Arc<DbClient>,
+    pub mem_pool: Mutex<Mempool>,
+    pub peers: Mutex<PeerBook>,
+    pub store: Arc<dyn Store>,
+    /// Endpoint peers reach this node at, if it serves the rpc.
+    pub endpoint: Option<Endpoint>,
+    /// Transactions already admitted, so that gossip stops at nodes that relayed them.
+    pub seen_txs: Mutex<SeenSet<Signature>>,
+    pub connections: ConnectionPool,
+    pub delivery: DeliveryMetrics,
+    /// Fed with chain updates and admitted transactions, drained by the subscription rpcs.
+    pub events: broadcast::Sender<LedgerEvent>,
+    /// Key the block rewards and fees go to, the node does not build blocks without one.
+    pub miner: Option<Keypair>,
 }
 
 impl Ledger {
+    pub const SEEN_TXS_CAPACITY: usize = 20_000;
+
     pub fn run_rpc(self: Arc<Ledger>, socket: SocketAddr) -> tokio::task::JoinHandle<Result<()>> {
         let rpc = GulfStreamRpc {
             ledger: self.clone(),
@@ -42,14 +67,6 @@ impl Ledger {
         })
     }
 
-    pub fn run_db(self: Arc<Ledger>) -> tokio::task::JoinHandle<Result<()>> {
-        tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
-            }
-        })
-    }
-
     pub fn run_logs(self: Arc<Ledger>) -> tokio::task::JoinHandle<()> {
         tokio::spawn(async move {
             loop {
@@ -65,25 +82,145 @@ impl Ledger {
                     "Ledger mempool : {} txs",
                     ledger.clone().mem_pool.lock().await.len(),
                 );
+                println!("Ledger broadcast : {}", ledger.delivery);
+                println!(
+                    "Ledger orphans : {} blocks",
+                    ledger.clone().state.lock().await.get_orphan_count(),
+                );
                 tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
             }
         })
     }
 
+    /// Single entry point for new blocks, mined locally or received from a peer. Once the
+    /// block is in the chain, with the orphans waiting for it, transactions the canonical
+    /// chain gained leave the mem pool and transactions of orphaned blocks whose nonce is
+    /// still unused go back in it.
+    pub async fn accept_block(&self, block: &Block) -> Result<ChainUpdate, GulfStreamError> {
+        let (update, connected_orphans, latest_link) = {
+            let mut state_guard = self.state.lock().await;
+            let mut update = state_guard.try_insert(block)?;
+            let (connected_orphans, orphans_update) = state_guard.connect_orphans(&block.blockhash);
+            update.merge(orphans_update);
+            (update, connected_orphans, state_guard.get_latest())
+        };
+        let inserted_blocks: Vec<&Block> = std::iter::once(block)
+            .chain(connected_orphans.iter())
+            .collect();
+        self.persist_blocks(&inserted_blocks, &update, &latest_link)
+            .await;
+        if !update.connected.is_empty() || update.is_reorg() {
+            self.publish(LedgerEvent::ChainUpdated {
+                update: Arc::new(update.clone()),
+                head: latest_link.clone(),
+            });
+        }
+        let mut mempool_guard = self.mem_pool.lock().await;
+        mempool_guard.remove_included(&update.connected_transactions());
+        update
+            .orphaned_transactions()
+            .into_iter()
+            .filter(|tx| !tx.is_coinbase() && tx.nonce >= latest_link.get_nonce(&tx.payer))
+            .for_each(|orphaned_tx| {
+                if mempool_guard.insert(orphaned_tx.clone()).is_ok() {
+                    self.publish(LedgerEvent::TransactionAdmitted(Box::new(orphaned_tx)));
+                }
+            });
+        Ok(update)
+    }
+
+    /// A failed write only costs the block on restart, peers can send it again, so it is
+    /// logged rather than rejecting a block the chain already holds.
+    async fn persist_blocks(&self, blocks: &[&Block], update: &ChainUpdate, latest_link: &Link) {
+        for block in blocks {
+            if let Err(err) = self.store.insert_block(block).await {
+                println!("Failed to store block {} : {}", block.blockhash, err);
+            }
+        }
+        if update.connected.is_empty() {
+            return;
+        }
+        if let Err(err) = self.store.set_head(&latest_link.block.blockhash).await {
+            println!(
+                "Failed to store head {} : {}",
+                latest_link.block.blockhash, err
+            );
+        }
+        if let Err(err) = self
+            .store
+            .index_history(update, &latest_link.block.blockhash)
+            .await
+        {
+            println!("Failed to index history : {}", err);
+        }
+        let accounts = update
+            .connected
+            .iter()
+            .chain(update.disconnected.iter())
+            .flat_map(|block| Transaction::get_involved_pk_from_txs(&block.transactions))
+            .map(|pk| {
+                let account = latest_link.get_account(&pk);
+                (pk, account)
+            })
+            .collect();
+        if let Err(err) = self.store.set_accounts(&accounts).await {
+            println!("Failed to store accounts : {}", err);
+        }
+    }
+
+    /// `sender` field of the requests this node sends.
+    pub fn get_sender(&self) -> String {
+        self.endpoint
+            .as_ref()
+            .map(|endpoint| endpoint.uri().to_string())
+            .unwrap_or_default()
+    }
+
+    pub async fn get_chain_id(&self) -> String {
+        self.state.lock().await.get_chain_id().to_string()
+    }
+
+    pub async fn get_genesis_hash(&self) -> Blockhash {
+        self.state.lock().await.get_genesis_hash().clone()
+    }
+
+    /// Relays an admitted transaction to every peer but the one it came from.
+    pub fn gossip_transaction(self: Arc<Ledger>, tx: Transaction, from: String) {
+        tokio::spawn(async move {
+            let request = SendTransactionRequest {
+                tx: Some(tx.into()),
+                sender: self.get_sender(),
+            };
+            if let Err(failed) = self.broadcast_transaction(request, &from).await {
+                println!("Transaction gossip failed for : {:?}", failed);
+            }
+        });
+    }
+
+    /// Drops the mem pool transactions that can no longer be included from `next_index` on.
+    pub async fn evict_expired_transactions(&self, next_index: u64) {
+        self.mem_pool.lock().await.evict_expired(next_index);
+    }
+
     pub fn run_node(self: Arc<Ledger>) -> tokio::task::JoinHandle<Result<()>> {
         tokio::spawn(async move {
             loop {
                 let ledger = self.clone();
-                let latest_block = ledger.clone().state.lock().await.get_latest().block.clone();
+                if let Ok(accepted) = ledger.sync().await {
+                    if accepted > 0 {
+                        println!("Synced {} blocks", accepted);
+                    }
+                }
+                let latest_link = ledger.clone().state.lock().await.get_latest();
+                ledger
+                    .evict_expired_transactions(latest_link.block.index + 1)
+                    .await;
 
-                if let Some(block) = ledger
-                    .clone()
-                    .try_build_block(latest_block.index, &latest_block.blockhash)
-                    .await
-                {
+                if let Some(block) = ledger.clone().try_build_block(latest_link).await {
                     match self
                         .broadcast(SendBlockRequest {
                             block: Some(block.try_into()?),
+                            sender: self.get_sender(),
                         })
                         .await
                     {
@@ -99,48 +236,58 @@ impl Ledger {
 
 #[tonic::async_trait]
 impl BlockBuilder for Ledger {
-    async fn try_build_block(
-        &self,
-        previous_index: u64,
-        previous_blockhash: &Blockhash,
-    ) -> Option<Block> {
-        let can_build_block = self.mem_pool.lock().await.len() > 0;
-        return if can_build_block {
-            let mut nonce = 0;
-            let mut txs = self
-                .mem_pool
-                .lock()
-                .await
-                .clone()
-                .into_iter()
-                .filter(|tx| {
-                    return tx.blockheight == previous_index + 1;
-                })
-                .collect::<Vec<Transaction>>();
+    async fn try_build_block(&self, previous_link: Arc<Link>) -> Option<Block> {
+        let previous_index = previous_link.block.index;
+        let previous_blockhash = &previous_link.block.blockhash;
+        // Every block pays its producer, so only a miner builds blocks. It does without traffic
+        // too, so that rewards keep being paid on schedule.
+        let miner = match &self.miner {
+            Some(miner) => miner,
+            None => return None,
+        };
+        let mut nonce = 0;
+        let mut txs = self
+            .mem_pool
+            .lock()
+            .await
+            .get_by_fee()
+            .into_iter()
+            .filter(|tx| {
+                return tx.can_be_included_at(previous_index + 1);
+            })
+            .collect::<Vec<Transaction>>();
 
-            txs.sort_by(|a, b| b.cmp(a));
+        let involved_pk: Vec<PublicKey> = Transaction::get_involved_pk_from_txs(&txs);
 
-            let involved_pk: Vec<PublicKey> = Transaction::get_involved_pk_from_txs(&txs);
+        let mut balance_deltas = previous_link.get_balances(&involved_pk);
 
-            let mut balance_deltas = self
-                .state
-                .lock()
-                .await
-                .get_latest()
-                .get_balances(&involved_pk);
+        let payers = txs.iter().map(|tx| tx.payer.clone()).collect();
+        let mut nonces = previous_link.get_nonces(&payers);
 
-            let mut valid_txs: Vec<Transaction> = vec![];
+        let mut valid_txs: Vec<Transaction> = vec![];
 
-            txs.into_iter().for_each(|tx| {
-                let tx_balance_deltas = tx.get_balance_deltas();
+        // A payer's transactions only go in nonce order, so keep passing over the remaining
+        // ones as long as the previous pass made some of them includable.
+        loop {
+            let mut included_any = false;
+            txs.retain(|tx| {
+                if nonces.get(&tx.payer).copied().unwrap_or_default() != tx.nonce {
+                    return true;
+                }
+                let tx_balance_deltas = match tx.get_balance_deltas() {
+                    Ok(deltas) => deltas,
+                    Err(_) => return false,
+                };
                 if tx_balance_deltas.iter().fold(true, |res, (pk, delta)| {
                     if res {
                         if delta.is_positive_or_nil() {
                             return true;
                         } else {
                             if let Some(balance_delta) = balance_deltas.get_mut(pk) {
-                                let delta_if_executed = delta.add(balance_delta.to_owned());
-                                return delta_if_executed.is_positive_or_nil();
+                                return matches!(
+                                    delta.checked_add(balance_delta.to_owned()),
+                                    Ok(delta_if_executed) if delta_if_executed.is_positive_or_nil()
+                                );
                             } else {
                                 return false;
                             }
@@ -149,90 +296,148 @@ impl BlockBuilder for Ledger {
                         return res;
                     }
                 }) {
-                    valid_txs.push(tx);
                     tx_balance_deltas.iter().for_each(|(pk, delta)| {
                         if let Some(balance_delta) = balance_deltas.get_mut(pk) {
-                            let delta_if_executed = delta.add(balance_delta.to_owned());
-                            if delta_if_executed.is_positive_or_nil() {
-                                *balance_delta = delta_if_executed;
+                            if let Ok(delta_if_executed) =
+                                delta.checked_add(balance_delta.to_owned())
+                            {
+                                if delta_if_executed.is_positive_or_nil() {
+                                    *balance_delta = delta_if_executed;
+                                }
                             }
                         } else {
                             balance_deltas.insert(pk.to_owned(), delta.to_owned());
                         }
                     });
+                    nonces.insert(tx.payer.clone(), tx.nonce + 1);
+                    valid_txs.push(tx.clone());
+                    included_any = true;
+                    false
+                } else {
+                    true
                 }
             });
-
-            if valid_txs.is_empty() {
-                return None;
+            if !included_any {
+                break;
             }
+        }
 
-            let raw_txs = Transaction::get_raw_txs(&valid_txs);
-            loop {
-                let blockhash = Blockhash::from_raw_data(
-                    previous_index + 1,
-                    previous_blockhash,
-                    &raw_txs,
+        let coinbase = {
+            let state = self.state.lock().await;
+            let fees = Block::get_fees_from_txs(&valid_txs).ok()?;
+            state
+                .get_policy()
+                .create_coinbase(miner, state.get_chain_id(), previous_index + 1, fees)
+                .ok()?
+        };
+        valid_txs.insert(0, coinbase);
+
+        let difficulty = next_difficulty(&previous_link);
+        let timestamp = unix_timestamp().max(Blockchain::median_time_past(&previous_link) + 1);
+        let raw_txs = Transaction::get_raw_txs(&valid_txs);
+        loop {
+            let blockhash = Blockhash::from_raw_data(
+                previous_index + 1,
+                previous_blockhash,
+                &raw_txs,
+                nonce,
+                difficulty,
+                timestamp,
+            );
+            if blockhash.is_valid(difficulty) {
+                println!("Blockhash found : {}", blockhash);
+                let block = Block {
+                    index: previous_index + 1,
+                    blockhash,
+                    previous_blockhash: previous_blockhash.to_owned(),
+                    transactions: valid_txs.clone(),
                     nonce,
-                );
-                if blockhash.is_valid(1) {
-                    println!("Blockhash found : {}", blockhash);
-                    let block = Block {
-                        index: previous_index + 1,
-                        blockhash,
-                        previous_blockhash: previous_blockhash.to_owned(),
-                        transactions: valid_txs.clone(),
-                        nonce,
-                    };
+                    difficulty,
+                    timestamp,
+                };
 
-                    return match self.state.lock().await.try_insert(&block) {
-                        Ok(_) => {
-                            {
-                                let mut mempool_guard = self.mem_pool.lock().await;
-                                valid_txs.iter().for_each(|valid_tx| {
-                                    if let Some(index) = mempool_guard
-                                        .iter()
-                                        .position(|tx| tx.signature.eq(&valid_tx.signature))
-                                    {
-                                        mempool_guard.swap_remove(index);
-                                    }
-                                });
-                            }
-                            Some(block)
-                        }
-                        Err(_) => None,
-                    };
-                } else {
-                    nonce += 1;
-                }
+                return match self.accept_block(&block).await {
+                    Ok(_) => Some(block),
+                    Err(_) => None,
+                };
+            } else {
+                nonce += 1;
             }
-        } else {
-            None
-        };
+        }
     }
 }
 
 #[tonic::async_trait]
 pub trait BlockBuilder {
-    async fn try_build_block(
-        &self,
-        previous_index: u64,
-        previous_blockhash: &Blockhash,
-    ) -> Option<Block>;
+    async fn try_build_block(&self, previous_link: Arc<Link>) -> Option<Block>;
+}
+
+/// Where a transaction stands from this node's point of view.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionStatus {
+    /// Waiting in the mem pool.
+    Pending,
+    /// In the canonical chain, `confirmations` counts its block and the ones built on it.
+    Confirmed {
+        blockhash: Blockhash,
+        index: u64,
+        confirmations: u64,
+    },
+    /// Admitted once but neither in the mem pool nor in the canonical chain anymore.
+    Dropped(String),
 }
 
 #[tonic::async_trait]
 pub trait Explorer {
-    async fn get_transaction_history(&self) -> Vec<Transaction>;
+    async fn get_transaction_history(
+        &self,
+        query: &HistoryQuery,
+    ) -> Result<HistoryPage, GulfStreamError>;
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<(Transaction, TransactionStatus)>, GulfStreamError>;
 }
 
 #[tonic::async_trait]
 impl Explorer for Ledger {
-    async fn get_transaction_history(&self) -> Vec<Transaction> {
-        self.state
-            .lock()
-            .await
-            .get_latest()
-            .get_transaction_history()
+    async fn get_transaction_history(
+        &self,
+        query: &HistoryQuery,
+    ) -> Result<HistoryPage, GulfStreamError> {
+        self.store.get_history(query).await
+    }
+
+    async fn get_transaction(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<(Transaction, TransactionStatus)>, GulfStreamError> {
+        // The history index follows the chain, read first it never gets ahead of the head.
+        let confirmed = self.store.get_tx_in_history(signature).await?;
+        let latest_link = self.state.lock().await.get_latest();
+        if let Some((blockhash, entry)) = confirmed {
+            let index = entry.cursor.block_index;
+            let status = TransactionStatus::Confirmed {
+                blockhash,
+                index,
+                confirmations: latest_link.block.index.saturating_sub(index) + 1,
+            };
+            return Ok(Some((entry.tx, status)));
+        }
+        if let Some(tx) = self.mem_pool.lock().await.get(signature) {
+            return Ok(Some((tx.clone(), TransactionStatus::Pending)));
+        }
+        let tx = match self.store.get_tx(signature).await? {
+            Some(tx) => tx,
+            None => return Ok(None),
+        };
+        let reason = if tx.nonce < latest_link.get_nonce(&tx.payer) {
+            "Nonce used by another transaction"
+        } else if tx.is_expired_at(latest_link.block.index + 1) {
+            "Validity window expired"
+        } else {
+            "Not in the mem pool"
+        };
+        Ok(Some((tx, TransactionStatus::Dropped(reason.into()))))
     }
 }
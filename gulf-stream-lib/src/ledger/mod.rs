@@ -1 +1,4 @@
+pub mod discovery;
+pub mod events;
 pub mod ledger;
+pub mod sync;
@@ -0,0 +1,211 @@
+use crate::err::GulfStreamError;
+use crate::ledger::ledger::Ledger;
+use crate::pb::node_client::NodeClient;
+use crate::pb::{GetBlockByHashRequest, GetBlocksRequest, GetLatestBlockRequest};
+use crate::peer::peers::PeerBook;
+use crate::state::block::Block;
+use crate::state::blockhash::Blockhash;
+use crate::utils::time::unix_timestamp;
+
+use std::cmp::Reverse;
+
+use tonic::transport::{Channel, Endpoint};
+
+/// Most blocks a node sends back for a single `GetBlocks`.
+pub const MAX_BLOCKS_PER_REQUEST: u64 = 100;
+/// Most `GetBlocks` rounds against one peer per sync, the rest waits for the next sync.
+pub const MAX_SYNC_ROUNDS: usize = 100;
+
+#[tonic::async_trait]
+impl Synchronizer for Ledger {
+    async fn sync(&self) -> Result<u64, GulfStreamError> {
+        let nodes = self.peers.lock().await.get_active(unix_timestamp());
+        let mut peer_heads = vec![];
+        for endpoint in nodes.into_iter() {
+            match self.get_peer_head(&endpoint).await {
+                Ok((client, peer_head)) => peer_heads.push((endpoint, client, peer_head)),
+                Err(err) => {
+                    println!("Failed to reach {:?} : {}", endpoint.uri(), err);
+                    let uri = PeerBook::get_uri(&endpoint);
+                    let mut peers_guard = self.peers.lock().await;
+                    if err.is_misbehavior() {
+                        peers_guard.ban(&uri, unix_timestamp());
+                    } else {
+                        peers_guard.report_failure(&uri, unix_timestamp());
+                    }
+                }
+            }
+        }
+        // Best peer first, the others usually have nothing left to send afterwards.
+        peer_heads.sort_by_key(|(_, _, peer_head)| Reverse(peer_head.index));
+
+        let mut accepted = 0;
+        for (endpoint, mut client, peer_head) in peer_heads.into_iter() {
+            match self.sync_with(&mut client, &peer_head).await {
+                Ok(count) => accepted += count,
+                Err(err) => {
+                    println!("Sync failed at block {} : {}", peer_head.index, err);
+                    if err.is_misbehavior() {
+                        let uri = PeerBook::get_uri(&endpoint);
+                        self.peers.lock().await.ban(&uri, unix_timestamp());
+                    }
+                }
+            }
+        }
+        Ok(accepted)
+    }
+}
+
+impl Ledger {
+    async fn get_peer_head(
+        &self,
+        endpoint: &Endpoint,
+    ) -> Result<(NodeClient<Channel>, Block), GulfStreamError> {
+        let mut client = self.connections.get(endpoint).await?;
+        self.handshake(&mut client).await?;
+        let peer_head = client
+            .get_latest_block(GetLatestBlockRequest {})
+            .await
+            .map_err(|err| GulfStreamError::map(err.message()))?
+            .into_inner()
+            .block
+            .ok_or(GulfStreamError::BlockNotFound)?
+            .try_into()?;
+        Ok((client, peer_head))
+    }
+
+    /// Fetches from `endpoint` the ancestors of an orphan we do not know, accepting them
+    /// connects the orphan.
+    pub async fn request_missing_ancestors(
+        &self,
+        endpoint: Endpoint,
+        orphan: &Block,
+    ) -> Result<u64, GulfStreamError> {
+        let mut client = self.connections.get(&endpoint).await?;
+        if let Err(err) = self.handshake(&mut client).await {
+            if err.is_misbehavior() {
+                let uri = PeerBook::get_uri(&endpoint);
+                self.peers.lock().await.ban(&uri, unix_timestamp());
+            }
+            return Err(err);
+        }
+        let ancestors = self.get_missing_ancestors(&mut client, orphan).await?;
+        for ancestor in ancestors.iter() {
+            match self.accept_block(ancestor).await {
+                Ok(_) | Err(GulfStreamError::BlockAlreadyKnown) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(ancestors.len() as u64)
+    }
+
+    async fn knows_block(&self, blockhash: &Blockhash) -> bool {
+        self.state.lock().await.get_block(blockhash).is_some()
+    }
+
+    /// Downloads the peer's canonical chain from our head index on, filling in the ancestors
+    /// of blocks whose parent we do not know, until the peer head is in our tree. Each round
+    /// must move past the previous one, a peer that does not is misbehaving.
+    async fn sync_with(
+        &self,
+        client: &mut NodeClient<Channel>,
+        peer_head: &Block,
+    ) -> Result<u64, GulfStreamError> {
+        let mut accepted = 0;
+        let mut from_index = self.state.lock().await.get_latest().block.index + 1;
+        for _ in 0..MAX_SYNC_ROUNDS {
+            if self.knows_block(&peer_head.blockhash).await {
+                break;
+            }
+            let blocks: Vec<Block> = client
+                .get_blocks(GetBlocksRequest {
+                    from_index,
+                    count: MAX_BLOCKS_PER_REQUEST,
+                })
+                .await
+                .map_err(|err| GulfStreamError::map(err.message()))?
+                .into_inner()
+                .blocks
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?;
+            let last_block = match blocks.last() {
+                Some(last_block) => last_block,
+                None if peer_head.index >= from_index => return Err(GulfStreamError::WrongIndex),
+                None => {
+                    // The peer head is not above ours, it sits on a fork we never saw.
+                    accepted += self.accept_with_ancestors(client, peer_head).await?;
+                    break;
+                }
+            };
+            let is_in_range = blocks
+                .iter()
+                .enumerate()
+                .all(|(position, block)| block.index == from_index + position as u64);
+            if !is_in_range {
+                return Err(GulfStreamError::WrongIndex);
+            }
+            from_index = last_block.index + 1;
+            for block in blocks.iter() {
+                if !self.knows_block(&block.blockhash).await {
+                    accepted += self.accept_with_ancestors(client, block).await?;
+                }
+            }
+        }
+        Ok(accepted)
+    }
+
+    async fn accept_with_ancestors(
+        &self,
+        client: &mut NodeClient<Channel>,
+        block: &Block,
+    ) -> Result<u64, GulfStreamError> {
+        let ancestors = self.get_missing_ancestors(client, block).await?;
+        for ancestor in ancestors.iter() {
+            self.accept_block(ancestor).await?;
+        }
+        self.accept_block(block).await?;
+        Ok(ancestors.len() as u64 + 1)
+    }
+
+    /// Ancestors of `block` we do not know, oldest first.
+    async fn get_missing_ancestors(
+        &self,
+        client: &mut NodeClient<Channel>,
+        block: &Block,
+    ) -> Result<Vec<Block>, GulfStreamError> {
+        let mut ancestors = vec![];
+        let mut previous_blockhash = block.previous_blockhash.clone();
+        let mut child_index = block.index;
+        while !self.knows_block(&previous_blockhash).await {
+            let ancestor: Block = client
+                .get_block_by_hash(GetBlockByHashRequest {
+                    blockhash: previous_blockhash.clone().into(),
+                })
+                .await
+                .map_err(|err| GulfStreamError::map(err.message()))?
+                .into_inner()
+                .block
+                .ok_or(GulfStreamError::DidNotFindPreviousBlock)?
+                .try_into()?;
+            if ancestor.index + 1 != child_index {
+                return Err(GulfStreamError::WrongIndex);
+            }
+            if ancestor.index == 0 {
+                // Peers on another genesis share no ancestor with us.
+                return Err(GulfStreamError::DidNotFindPreviousBlock);
+            }
+            child_index = ancestor.index;
+            previous_blockhash = ancestor.previous_blockhash.clone();
+            ancestors.push(ancestor);
+        }
+        ancestors.reverse();
+        Ok(ancestors)
+    }
+}
+
+#[tonic::async_trait]
+pub trait Synchronizer {
+    /// Catches up with the peers, returns the number of blocks accepted.
+    async fn sync(&self) -> Result<u64, GulfStreamError>;
+}
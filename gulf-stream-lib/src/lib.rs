@@ -1,7 +1,9 @@
 pub mod ed25519;
 pub mod err;
 pub mod ledger;
+pub mod mempool;
 pub mod pb;
+pub mod peer;
 pub mod rpc;
 pub mod state;
 pub mod utils;
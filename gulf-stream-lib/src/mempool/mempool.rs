@@ -0,0 +1,293 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::ed25519::{publickey::PublicKey, signature::Signature};
+use crate::err::GulfStreamError;
+use crate::state::transaction::Transaction;
+
+/// Pending transactions, indexed by signature, by fee and by payer nonce.
+pub struct Mempool {
+    max_size: usize,
+    max_per_sender: usize,
+    txs: HashMap<Signature, Transaction>,
+    by_fee: BTreeSet<(u64, Signature)>,
+    by_sender: HashMap<PublicKey, BTreeMap<u64, Signature>>,
+}
+
+impl Mempool {
+    pub const DEFAULT_MAX_SIZE: usize = 5000;
+    pub const DEFAULT_MAX_PER_SENDER: usize = 64;
+
+    pub fn new(max_size: usize, max_per_sender: usize) -> Self {
+        Self {
+            max_size,
+            max_per_sender,
+            txs: HashMap::new(),
+            by_fee: BTreeSet::new(),
+            by_sender: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+
+    pub fn contains(&self, signature: &Signature) -> bool {
+        self.txs.contains_key(signature)
+    }
+
+    pub fn get(&self, signature: &Signature) -> Option<&Transaction> {
+        self.txs.get(signature)
+    }
+
+    /// Whether `insert` would admit the transaction, without touching the pool.
+    pub fn check_insert(&self, tx: &Transaction) -> Result<(), GulfStreamError> {
+        if self.contains(&tx.signature) {
+            return Err(GulfStreamError::TxAlreadyInMempool);
+        }
+
+        if let Some(replaced) = self.get_by_nonce(&tx.payer, tx.nonce) {
+            if tx.gas <= replaced.gas {
+                return Err(GulfStreamError::ReplacementFeeTooLow);
+            }
+        } else {
+            let pending = self.by_sender.get(&tx.payer).map_or(0, BTreeMap::len);
+            if pending >= self.max_per_sender {
+                return Err(GulfStreamError::TooManyPendingTxs);
+            }
+            if self.len() >= self.max_size {
+                match self.by_fee.first() {
+                    Some((lowest_gas, _)) if *lowest_gas < tx.gas => {}
+                    _ => return Err(GulfStreamError::MempoolFull),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Admits a transaction. A transaction reusing a pending payer nonce replaces the pending
+    /// one if it pays more gas, and a full pool makes room by evicting its cheapest transaction.
+    pub fn insert(&mut self, tx: Transaction) -> Result<(), GulfStreamError> {
+        self.check_insert(&tx)?;
+
+        if let Some(replaced) = self.get_by_nonce(&tx.payer, tx.nonce).cloned() {
+            self.remove(&replaced.signature);
+        } else if self.len() >= self.max_size {
+            self.evict_lowest_fee();
+        }
+        self.by_fee.insert(Self::fee_key(&tx));
+        self.by_sender
+            .entry(tx.payer.clone())
+            .or_default()
+            .insert(tx.nonce, tx.signature.clone());
+        self.txs.insert(tx.signature.clone(), tx);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, signature: &Signature) -> Option<Transaction> {
+        let tx = self.txs.remove(signature)?;
+        self.by_fee.remove(&Self::fee_key(&tx));
+        if let Some(nonces) = self.by_sender.get_mut(&tx.payer) {
+            nonces.remove(&tx.nonce);
+            if nonces.is_empty() {
+                self.by_sender.remove(&tx.payer);
+            }
+        }
+        Some(tx)
+    }
+
+    /// Drops the transactions a block included, along with the pending ones whose payer nonce
+    /// the block used up. Coinbases use up no nonce, so they leave the producer's pending
+    /// transactions alone.
+    pub fn remove_included(&mut self, included_txs: &[Transaction]) {
+        for included_tx in included_txs.iter().filter(|tx| !tx.is_coinbase()) {
+            self.remove(&included_tx.signature);
+            let used_up: Vec<Signature> = self
+                .by_sender
+                .get(&included_tx.payer)
+                .map(|nonces| {
+                    nonces
+                        .range(..=included_tx.nonce)
+                        .map(|(_, signature)| signature.clone())
+                        .collect()
+                })
+                .unwrap_or_default();
+            used_up.iter().for_each(|signature| {
+                self.remove(signature);
+            });
+        }
+    }
+
+    /// Drops the transactions that can no longer be included from `next_index` on.
+    pub fn evict_expired(&mut self, next_index: u64) {
+        let expired: Vec<Signature> = self
+            .txs
+            .values()
+            .filter(|tx| tx.is_expired_at(next_index))
+            .map(|tx| tx.signature.clone())
+            .collect();
+        expired.iter().for_each(|signature| {
+            self.remove(signature);
+        });
+    }
+
+    pub fn get_by_nonce(&self, payer: &PublicKey, nonce: u64) -> Option<&Transaction> {
+        self.by_sender
+            .get(payer)
+            .and_then(|nonces| nonces.get(&nonce))
+            .and_then(|signature| self.txs.get(signature))
+    }
+
+    /// Pending transactions, highest gas first.
+    pub fn get_by_fee(&self) -> Vec<Transaction> {
+        self.txs_by_fee().cloned().collect()
+    }
+
+    fn txs_by_fee(&self) -> impl Iterator<Item = &Transaction> {
+        self.by_fee
+            .iter()
+            .rev()
+            .filter_map(|(_, signature)| self.txs.get(signature))
+    }
+
+    fn evict_lowest_fee(&mut self) {
+        if let Some((_, signature)) = self.by_fee.first().cloned() {
+            self.remove(&signature);
+        }
+    }
+
+    fn fee_key(tx: &Transaction) -> (u64, Signature) {
+        (tx.gas, tx.signature.clone())
+    }
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_SIZE, Self::DEFAULT_MAX_PER_SENDER)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::{amount::Amount, genesis::Genesis};
+    use crate::utils::fixtures::*;
+
+    #[test]
+    fn orders_by_fee() {
+        let mut mempool = Mempool::default();
+        let (alice, bob) = (keypair(), keypair());
+        mempool.insert(paying(&alice, 0, 1)).unwrap();
+        mempool.insert(paying(&bob, 0, 3)).unwrap();
+        mempool.insert(paying(&alice, 1, 2)).unwrap();
+
+        let gas: Vec<u64> = mempool.get_by_fee().iter().map(|tx| tx.gas).collect();
+        assert_eq!(gas, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn deduplicates_by_signature() {
+        let mut mempool = Mempool::default();
+        let pending = paying(&keypair(), 0, 1);
+        mempool.insert(pending.clone()).unwrap();
+        assert_eq!(
+            mempool.insert(pending),
+            Err(GulfStreamError::TxAlreadyInMempool)
+        );
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn replaces_by_fee() {
+        let mut mempool = Mempool::default();
+        let alice = keypair();
+        let pending = paying(&alice, 0, 2);
+        mempool.insert(pending.clone()).unwrap();
+
+        assert_eq!(
+            mempool.insert(paying(&alice, 0, 1)),
+            Err(GulfStreamError::ReplacementFeeTooLow)
+        );
+
+        let replacement = paying(&alice, 0, 3);
+        mempool.insert(replacement.clone()).unwrap();
+        assert_eq!(mempool.len(), 1);
+        assert!(!mempool.contains(&pending.signature));
+        assert_eq!(
+            mempool.get_by_nonce(&replacement.payer, 0),
+            Some(&replacement)
+        );
+    }
+
+    #[test]
+    fn limits_pending_per_sender() {
+        let mut mempool = Mempool::new(10, 2);
+        let alice = keypair();
+        mempool.insert(paying(&alice, 0, 1)).unwrap();
+        mempool.insert(paying(&alice, 1, 1)).unwrap();
+        assert_eq!(
+            mempool.insert(paying(&alice, 2, 1)),
+            Err(GulfStreamError::TooManyPendingTxs)
+        );
+        mempool.insert(paying(&keypair(), 0, 1)).unwrap();
+    }
+
+    #[test]
+    fn evicts_lowest_fee_when_full() {
+        let mut mempool = Mempool::new(2, 2);
+        let cheapest = paying(&keypair(), 0, 1);
+        mempool.insert(cheapest.clone()).unwrap();
+        mempool.insert(paying(&keypair(), 0, 5)).unwrap();
+
+        assert_eq!(
+            mempool.insert(paying(&keypair(), 0, 1)),
+            Err(GulfStreamError::MempoolFull)
+        );
+        let richer = paying(&keypair(), 0, 3);
+        mempool.check_insert(&richer).unwrap();
+        assert!(mempool.contains(&cheapest.signature));
+        mempool.insert(richer).unwrap();
+        assert_eq!(mempool.len(), 2);
+        assert!(!mempool.contains(&cheapest.signature));
+    }
+
+    #[test]
+    fn removes_included_and_used_up_nonces() {
+        let mut mempool = Mempool::default();
+        let alice = keypair();
+        let stale = paying(&alice, 0, 1);
+        let pending = paying(&alice, 2, 1);
+        mempool.insert(stale.clone()).unwrap();
+        mempool.insert(pending.clone()).unwrap();
+
+        mempool.remove_included(&[paying(&alice, 1, 1)]);
+        assert!(!mempool.contains(&stale.signature));
+        assert!(mempool.contains(&pending.signature));
+    }
+
+    #[test]
+    fn keeps_producer_txs_on_coinbase() {
+        let mut mempool = Mempool::default();
+        let pending = paying(&producer(), 0, 1);
+        mempool.insert(pending.clone()).unwrap();
+
+        let coinbase = policy()
+            .create_coinbase(&producer(), Genesis::DEFAULT_CHAIN_ID, 1, Amount::ZERO)
+            .unwrap();
+        mempool.remove_included(&[coinbase]);
+        assert!(mempool.contains(&pending.signature));
+    }
+
+    #[test]
+    fn evicts_expired() {
+        let mut mempool = Mempool::default();
+        mempool.insert(paying(&keypair(), 0, 1)).unwrap();
+        mempool.evict_expired(1 + Transaction::MAX_VALIDITY_WINDOW);
+        assert_eq!(mempool.len(), 1);
+        mempool.evict_expired(2 + Transaction::MAX_VALIDITY_WINDOW);
+        assert!(mempool.is_empty());
+    }
+}
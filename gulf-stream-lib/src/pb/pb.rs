@@ -21,6 +21,8 @@ impl TryInto<crate::state::block::Block> for Block {
             blockhash: self.blockhash.into(),
             previous_blockhash: self.previous_blockhash.into(),
             nonce: self.nonce,
+            difficulty: self.difficulty,
+            timestamp: self.timestamp,
         })
     }
 }
@@ -33,6 +35,8 @@ impl From<crate::state::block::Block> for Block {
             blockhash: value.blockhash.into(),
             previous_blockhash: value.previous_blockhash.into(),
             nonce: value.nonce,
+            difficulty: value.difficulty,
+            timestamp: value.timestamp,
         }
     }
 }
@@ -42,11 +46,13 @@ impl TryInto<crate::state::transaction::Transaction> for Transaction {
 
     fn try_into(self) -> Result<crate::state::transaction::Transaction, Self::Error> {
         Ok(crate::state::transaction::Transaction {
-            blockheight: self.blockheight,
+            valid_from: self.valid_from,
+            valid_until: self.valid_until,
             payer: PublicKey::deserialize(&mut self.payer.as_ref())?,
             msg: TransactionMessage::deserialize(&mut self.msg.as_ref())?,
             signature: Signature::deserialize(&mut self.signature.as_ref())?,
             gas: self.gas,
+            nonce: self.nonce,
         })
     }
 }
@@ -54,11 +60,13 @@ impl TryInto<crate::state::transaction::Transaction> for Transaction {
 impl From<crate::state::transaction::Transaction> for Transaction {
     fn from(value: crate::state::transaction::Transaction) -> Self {
         Self {
-            blockheight: value.blockheight,
+            valid_from: value.valid_from,
+            valid_until: value.valid_until,
             payer: value.payer.serialize(),
             msg: value.msg.serialize(),
             signature: value.signature.serialize(),
             gas: value.gas,
+            nonce: value.nonce,
         }
     }
 }
@@ -123,20 +131,22 @@ impl From<crate::state::block::TransactionState> for TransactionState {
 mod test {
 
     use crate::err;
+    use crate::state::amount::Amount;
 
     use super::*;
 
     #[test]
     fn pb_into_tx() {
         let raw_tx = Transaction {
-            blockheight: 5,
+            valid_from: 5,
+            valid_until: 10,
             payer: vec![
                 110, 244, 56, 156, 170, 232, 45, 208, 70, 45, 1, 194, 190, 0, 250, 95, 236, 230,
                 83, 70, 255, 253, 51, 219, 174, 30, 197, 82, 243, 235, 57, 228,
             ],
             msg: TransactionMessage::Transfer {
                 to: PublicKey::random(),
-                amount: 77,
+                amount: Amount(77),
             }
             .serialize(),
             signature: vec![
@@ -146,6 +156,7 @@ mod test {
                 143, 156, 136, 167, 112, 144, 125, 173, 240, 8,
             ],
             gas: 65,
+            nonce: 0,
         };
 
         let tx: Result<crate::state::transaction::Transaction, err::GulfStreamError> =
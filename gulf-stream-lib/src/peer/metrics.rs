@@ -0,0 +1,62 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Outcome counters of the messages broadcast to peers, one count per peer and message.
+#[derive(Default)]
+pub struct DeliveryMetrics {
+    /// Accepted by the peer.
+    delivered: AtomicU64,
+    /// Received by the peer but refused, a block it already had for instance.
+    rejected: AtomicU64,
+    /// Attempts made again after a transport failure or a timeout.
+    retried: AtomicU64,
+    /// Still undelivered once the retries ran out.
+    failed: AtomicU64,
+}
+
+impl DeliveryMetrics {
+    pub fn record_delivered(&self) {
+        self.delivered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_rejected(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_retried(&self, count: u64) {
+        self.retried.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn record_failed(&self, count: u64) {
+        self.failed.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn get_delivered(&self) -> u64 {
+        self.delivered.load(Ordering::Relaxed)
+    }
+
+    pub fn get_rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    pub fn get_retried(&self) -> u64 {
+        self.retried.load(Ordering::Relaxed)
+    }
+
+    pub fn get_failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+}
+
+impl Display for DeliveryMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "delivered = {}, rejected = {}, retried = {}, failed = {}",
+            self.get_delivered(),
+            self.get_rejected(),
+            self.get_retried(),
+            self.get_failed()
+        )
+    }
+}
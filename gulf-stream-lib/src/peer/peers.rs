@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use tonic::transport::Endpoint;
+
+use crate::err::GulfStreamError;
+
+struct Peer {
+    endpoint: Endpoint,
+    /// Consecutive failed exchanges.
+    failures: u32,
+    /// Unix timestamp before which the peer is not contacted.
+    retry_at: u64,
+}
+
+/// Peers this node talks to. A peer that fails to answer is retried after an exponential
+/// backoff and forgotten after `MAX_FAILURES` failures in a row, a misbehaving peer is
+/// banned and cannot be added back until the ban expires.
+pub struct PeerBook {
+    max_peers: usize,
+    /// Uri of this node, never added as a peer.
+    own_uri: Option<String>,
+    peers: HashMap<String, Peer>,
+    /// Uri of banned peers, with the unix timestamp their ban expires at.
+    banned: HashMap<String, u64>,
+}
+
+impl PeerBook {
+    pub const DEFAULT_MAX_PEERS: usize = 16;
+    /// Seconds before retrying a peer that failed once, doubled on each further failure.
+    pub const BASE_BACKOFF: u64 = 5;
+    pub const MAX_BACKOFF: u64 = 5 * 60;
+    pub const MAX_FAILURES: u32 = 8;
+    pub const BAN_DURATION: u64 = 60 * 60;
+
+    pub fn new(max_peers: usize, own_endpoint: Option<&Endpoint>) -> Self {
+        Self {
+            max_peers,
+            own_uri: own_endpoint.map(Self::get_uri),
+            peers: HashMap::new(),
+            banned: HashMap::new(),
+        }
+    }
+
+    /// Accepts `host:port` as well as a full uri, plain http is assumed.
+    pub fn parse_endpoint(address: &str) -> Result<Endpoint, GulfStreamError> {
+        let address = if address.contains("://") {
+            address.to_string()
+        } else {
+            format!("http://{}", address)
+        };
+        Endpoint::from_shared(address).map_err(|err| GulfStreamError::map(err.to_string()))
+    }
+
+    pub fn get_uri(endpoint: &Endpoint) -> String {
+        endpoint.uri().to_string()
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    pub fn contains(&self, uri: &str) -> bool {
+        self.peers.contains_key(uri)
+    }
+
+    pub fn get(&self, uri: &str) -> Option<Endpoint> {
+        self.peers.get(uri).map(|peer| peer.endpoint.clone())
+    }
+
+    /// False if the peer is this node, already known, banned, or the book is full.
+    pub fn can_add(&self, endpoint: &Endpoint, now: u64) -> bool {
+        let uri = Self::get_uri(endpoint);
+        self.own_uri.as_ref() != Some(&uri)
+            && !self.contains(&uri)
+            && !self.is_banned(&uri, now)
+            && self.len() < self.max_peers
+    }
+
+    /// Returns false if the peer cannot be added, see `can_add`.
+    pub fn add(&mut self, endpoint: Endpoint, now: u64) -> bool {
+        if !self.can_add(&endpoint, now) {
+            return false;
+        }
+        let uri = Self::get_uri(&endpoint);
+        self.peers.insert(
+            uri,
+            Peer {
+                endpoint,
+                failures: 0,
+                retry_at: 0,
+            },
+        );
+        true
+    }
+
+    pub fn remove(&mut self, uri: &str) {
+        self.peers.remove(uri);
+    }
+
+    /// Every peer, including those waiting for a retry.
+    pub fn get_all(&self) -> Vec<Endpoint> {
+        self.peers
+            .values()
+            .map(|peer| peer.endpoint.clone())
+            .collect()
+    }
+
+    /// Peers that can be contacted at `now`.
+    pub fn get_active(&self, now: u64) -> Vec<Endpoint> {
+        self.peers
+            .values()
+            .filter(|peer| peer.retry_at <= now)
+            .map(|peer| peer.endpoint.clone())
+            .collect()
+    }
+
+    pub fn report_success(&mut self, uri: &str) {
+        if let Some(peer) = self.peers.get_mut(uri) {
+            peer.failures = 0;
+            peer.retry_at = 0;
+        }
+    }
+
+    pub fn report_failure(&mut self, uri: &str, now: u64) {
+        let peer = match self.peers.get_mut(uri) {
+            Some(peer) => peer,
+            None => return,
+        };
+        peer.failures += 1;
+        if peer.failures >= Self::MAX_FAILURES {
+            self.remove(uri);
+            return;
+        }
+        let backoff = Self::BASE_BACKOFF
+            .checked_shl(peer.failures - 1)
+            .unwrap_or(Self::MAX_BACKOFF)
+            .min(Self::MAX_BACKOFF);
+        peer.retry_at = now + backoff;
+    }
+
+    pub fn ban(&mut self, uri: &str, now: u64) {
+        self.remove(uri);
+        self.banned
+            .insert(uri.to_string(), now + Self::BAN_DURATION);
+    }
+
+    pub fn is_banned(&self, uri: &str, now: u64) -> bool {
+        matches!(self.banned.get(uri), Some(banned_until) if *banned_until > now)
+    }
+}
+
+impl Default for PeerBook {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_PEERS, None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn endpoint(port: u16) -> Endpoint {
+        PeerBook::parse_endpoint(&format!("127.0.0.1:{}", port)).unwrap()
+    }
+
+    fn uri(port: u16) -> String {
+        PeerBook::get_uri(&endpoint(port))
+    }
+
+    #[test]
+    fn parses_addresses() {
+        assert_eq!(uri(50051), "http://127.0.0.1:50051/");
+        assert_eq!(
+            PeerBook::get_uri(&PeerBook::parse_endpoint("https://node.test:443").unwrap()),
+            "https://node.test:443/"
+        );
+    }
+
+    #[test]
+    fn refuses_self_duplicates_and_overflow() {
+        let mut peers = PeerBook::new(2, Some(&endpoint(1)));
+        assert!(!peers.add(endpoint(1), 0));
+        assert!(peers.add(endpoint(2), 0));
+        assert!(!peers.add(endpoint(2), 0));
+        assert!(peers.add(endpoint(3), 0));
+        assert!(!peers.add(endpoint(4), 0));
+        assert_eq!(peers.len(), 2);
+    }
+
+    #[test]
+    fn backs_off_then_forgets_failing_peer() {
+        let mut peers = PeerBook::default();
+        peers.add(endpoint(2), 0);
+
+        peers.report_failure(&uri(2), 100);
+        assert!(peers.get_active(100).is_empty());
+        assert_eq!(peers.get_active(100 + PeerBook::BASE_BACKOFF).len(), 1);
+
+        peers.report_failure(&uri(2), 200);
+        assert!(peers.get_active(200 + PeerBook::BASE_BACKOFF).is_empty());
+        assert_eq!(peers.get_active(200 + 2 * PeerBook::BASE_BACKOFF).len(), 1);
+
+        peers.report_success(&uri(2));
+        assert_eq!(peers.get_active(200).len(), 1);
+
+        (0..PeerBook::MAX_FAILURES).for_each(|_| peers.report_failure(&uri(2), 300));
+        assert!(peers.is_empty());
+    }
+
+    #[test]
+    fn ban_expires() {
+        let mut peers = PeerBook::default();
+        peers.add(endpoint(2), 0);
+        peers.ban(&uri(2), 10);
+        assert!(peers.is_empty());
+        assert!(!peers.add(endpoint(2), 10 + PeerBook::BAN_DURATION - 1));
+        assert!(peers.add(endpoint(2), 10 + PeerBook::BAN_DURATION));
+    }
+}
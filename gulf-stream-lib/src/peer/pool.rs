@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tonic::transport::{Channel, Endpoint};
+
+use super::peers::PeerBook;
+use crate::err::GulfStreamError;
+use crate::pb::node_client::NodeClient;
+
+/// Long lived clients, one per peer. A client multiplexes its requests over a single
+/// connection and is cheap to clone.
+#[derive(Default)]
+pub struct ConnectionPool {
+    clients: Mutex<HashMap<String, NodeClient<Channel>>>,
+}
+
+impl ConnectionPool {
+    pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+    pub async fn get(&self, endpoint: &Endpoint) -> Result<NodeClient<Channel>, GulfStreamError> {
+        let uri = PeerBook::get_uri(endpoint);
+        if let Some(client) = self.clients.lock().await.get(&uri) {
+            return Ok(client.clone());
+        }
+        let channel = endpoint
+            .clone()
+            .connect_timeout(Self::CONNECT_TIMEOUT)
+            .connect()
+            .await
+            .map_err(|err| GulfStreamError::map(err.to_string()))?;
+        let client = NodeClient::new(channel);
+        self.clients.lock().await.insert(uri, client.clone());
+        Ok(client)
+    }
+
+    /// Drops the client of a peer whose connection broke, the next `get` reconnects.
+    pub async fn remove(&self, endpoint: &Endpoint) {
+        self.clients
+            .lock()
+            .await
+            .remove(&PeerBook::get_uri(endpoint));
+    }
+}
@@ -1,37 +1,189 @@
+use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::ed25519::publickey::PublicKey;
+use crate::ed25519::signature::Signature;
 use crate::err::GulfStreamError;
-use crate::ledger::ledger::{Explorer, Ledger};
-use crate::pb::node_client::NodeClient;
+use crate::ledger::events::LedgerEvent;
+use crate::ledger::ledger::{Explorer, Ledger, TransactionStatus};
+use crate::ledger::sync::MAX_BLOCKS_PER_REQUEST;
 use crate::pb::node_server::Node;
 use crate::pb::*;
+use crate::peer::peers::PeerBook;
 use crate::state::block::Block;
-use crate::state::transaction::Transaction;
-use crate::utils::serde::BytesDeserialize;
+use crate::state::blockhash::Blockhash;
+use crate::state::transaction::{Transaction, TransactionMessage};
+use crate::store::history::{Direction, HistoryCursor, HistoryQuery};
+use crate::utils::serde::{BytesDeserialize, BytesSerialize};
+use crate::utils::time::unix_timestamp;
+use futures::future::join_all;
+use futures::{stream, Stream, StreamExt};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 use tonic::transport::Endpoint;
-use tonic::{Request, Response, Status};
+use tonic::{Code, Request, Response, Status};
 
 pub struct GulfStreamRpc {
     pub ledger: Arc<Ledger>,
 }
 
+pub type Subscription<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+/// Streams what `map` makes of each ledger event. A subscriber too slow to keep up gets
+/// an error and the stream ends, it has to resubscribe and catch up with the unary rpcs.
+/// The stream ends the same way on the first error `map` returns.
+fn subscription<T, F>(receiver: broadcast::Receiver<LedgerEvent>, map: F) -> Subscription<T>
+where
+    T: Send + 'static,
+    F: Fn(LedgerEvent) -> Result<Vec<T>, GulfStreamError> + Send + 'static,
+{
+    let events = stream::unfold(Some((receiver, map)), |state| async move {
+        let (mut receiver, map) = state?;
+        match receiver.recv().await {
+            Ok(event) => match map(event) {
+                Ok(items) => Some((Ok(items), Some((receiver, map)))),
+                Err(err) => Some((Err(err.into()), None)),
+            },
+            Err(RecvError::Lagged(missed)) => Some((
+                Err(Status::resource_exhausted(format!(
+                    "Subscriber missed {} events",
+                    missed
+                ))),
+                None,
+            )),
+            Err(RecvError::Closed) => None,
+        }
+    });
+    Box::pin(events.flat_map(|items| {
+        let items: Vec<Result<T, Status>> = match items {
+            Ok(items) => items.into_iter().map(Ok).collect(),
+            Err(status) => vec![Err(status)],
+        };
+        stream::iter(items)
+    }))
+}
+
 #[tonic::async_trait]
 impl Node for GulfStreamRpc {
+    type SubscribeBlocksStream = Subscription<BlockEvent>;
+    type SubscribeTransactionsStream = Subscription<TransactionState>;
+    type SubscribeAccountStream = Subscription<GetBalanceResponse>;
+
+    async fn subscribe_blocks(
+        &self,
+        _request: Request<SubscribeBlocksRequest>,
+    ) -> Result<Response<Self::SubscribeBlocksStream>, Status> {
+        let stream = subscription(self.ledger.subscribe(), |event| {
+            Ok(match event {
+                LedgerEvent::ChainUpdated { update, .. } => update
+                    .disconnected
+                    .iter()
+                    .map(|block| (block, false))
+                    .chain(update.connected.iter().map(|block| (block, true)))
+                    .map(|(block, connected)| BlockEvent {
+                        block: Some(block.clone().into()),
+                        connected,
+                    })
+                    .collect(),
+                LedgerEvent::TransactionAdmitted(_) => vec![],
+            })
+        });
+        return Ok(Response::new(stream));
+    }
+
+    async fn subscribe_transactions(
+        &self,
+        _request: Request<SubscribeTransactionsRequest>,
+    ) -> Result<Response<Self::SubscribeTransactionsStream>, Status> {
+        let stream = subscription(self.ledger.subscribe(), |event| {
+            Ok(match event {
+                LedgerEvent::ChainUpdated { update, .. } => update
+                    .connected_transactions()
+                    .into_iter()
+                    .map(|tx| TransactionState {
+                        state: State::Success.into(),
+                        tx: Some(tx.into()),
+                    })
+                    .collect(),
+                LedgerEvent::TransactionAdmitted(tx) => vec![TransactionState {
+                    state: State::Pending.into(),
+                    tx: Some((*tx).into()),
+                }],
+            })
+        });
+        return Ok(Response::new(stream));
+    }
+
+    async fn subscribe_account(
+        &self,
+        request: Request<SubscribeAccountRequest>,
+    ) -> Result<Response<Self::SubscribeAccountStream>, Status> {
+        let pk = PublicKey::deserialize(&mut &request.into_inner().address[..])
+            .map_err(GulfStreamError::map_to_status)?;
+        let stream = subscription(self.ledger.subscribe(), move |event| {
+            if !event.get_involved_pk().contains(&pk) {
+                return Ok(vec![]);
+            }
+            match event {
+                LedgerEvent::ChainUpdated { head, .. } => {
+                    let account = head.get_account(&pk);
+                    Ok(vec![GetBalanceResponse {
+                        balance: account
+                            .balance
+                            .to_u64()
+                            .ok_or(GulfStreamError::map("Balance Negative"))?,
+                        nonce: account.nonce,
+                    }])
+                }
+                LedgerEvent::TransactionAdmitted(_) => Ok(vec![]),
+            }
+        });
+        return Ok(Response::new(stream));
+    }
+
     async fn send_block(
         &self,
         request: Request<SendBlockRequest>,
     ) -> Result<Response<GenericResponse>, Status> {
+        let request = request.into_inner();
         let block: Block = request
-            .into_inner()
             .block
             .ok_or(GulfStreamError::BlockIsNotValid)
             .map_err(GulfStreamError::map_to_status)?
             .try_into()
             .map_err(GulfStreamError::map_to_status)?;
 
-        if let Err(err) = self.ledger.state.lock().await.try_insert(&block) {
-            return Err(err.into());
+        match self.ledger.accept_block(&block).await {
+            Ok(_) => {}
+            Err(GulfStreamError::DidNotFindPreviousBlock) => {
+                // The sender is self-declared, only dial it if it is already one of our peers.
+                let sender = match PeerBook::parse_endpoint(&request.sender) {
+                    Ok(sender) => self
+                        .ledger
+                        .peers
+                        .lock()
+                        .await
+                        .get(&PeerBook::get_uri(&sender)),
+                    Err(_) => None,
+                };
+                if let Some(sender) = sender {
+                    let ledger = self.ledger.clone();
+                    let orphan = block.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = ledger.request_missing_ancestors(sender, &orphan).await {
+                            println!("Failed to connect orphan {} : {}", orphan.blockhash, err);
+                        }
+                    });
+                }
+                let reply = GenericResponse {
+                    message: format!("Block {} kept as orphan", block.blockhash),
+                };
+                return Ok(Response::new(reply));
+            }
+            // Nothing proves the block came from `sender`, so it is not banned here. Peers are
+            // only banned for what they answer to requests we made.
+            Err(err) => return Err(err.into()),
         }
 
         let reply = GenericResponse {
@@ -66,50 +218,292 @@ impl Node for GulfStreamRpc {
         return Ok(Response::new(reply));
     }
 
+    async fn get_blocks(
+        &self,
+        request: Request<GetBlocksRequest>,
+    ) -> Result<Response<GetBlocksResponse>, Status> {
+        let request = request.into_inner();
+        let blocks = self.ledger.state.lock().await.get_blocks(
+            request.from_index,
+            request.count.min(MAX_BLOCKS_PER_REQUEST),
+        );
+        let reply = GetBlocksResponse {
+            blocks: blocks.into_iter().map(Into::into).collect(),
+        };
+        return Ok(Response::new(reply));
+    }
+
+    async fn get_peers(
+        &self,
+        _request: Request<GetPeersRequest>,
+    ) -> Result<Response<GetPeersResponse>, Status> {
+        let reply = GetPeersResponse {
+            peers: self
+                .ledger
+                .peers
+                .lock()
+                .await
+                .get_all()
+                .iter()
+                .map(PeerBook::get_uri)
+                .collect(),
+        };
+        return Ok(Response::new(reply));
+    }
+
+    async fn announce_peer(
+        &self,
+        request: Request<AnnouncePeerRequest>,
+    ) -> Result<Response<GenericResponse>, Status> {
+        let request = request.into_inner();
+        if Blockhash(request.genesis_hash) != self.ledger.get_genesis_hash().await {
+            return Err(GulfStreamError::NetworkMismatch.into());
+        }
+        let endpoint =
+            PeerBook::parse_endpoint(&request.endpoint).map_err(GulfStreamError::map_to_status)?;
+        // Anyone can announce any endpoint, it only takes a slot once it answered a handshake.
+        let can_add = self
+            .ledger
+            .peers
+            .lock()
+            .await
+            .can_add(&endpoint, unix_timestamp());
+        let added = can_add
+            && self.ledger.reach(&endpoint).await.is_ok()
+            && self
+                .ledger
+                .peers
+                .lock()
+                .await
+                .add(endpoint.clone(), unix_timestamp());
+        let reply = GenericResponse {
+            message: if added {
+                format!("Peer {} added", PeerBook::get_uri(&endpoint))
+            } else {
+                format!("Peer {} not added", PeerBook::get_uri(&endpoint))
+            },
+        };
+        return Ok(Response::new(reply));
+    }
+
+    async fn handshake(
+        &self,
+        request: Request<HandshakeRequest>,
+    ) -> Result<Response<HandshakeResponse>, Status> {
+        let request = request.into_inner();
+        let reply = HandshakeResponse {
+            chain_id: self.ledger.get_chain_id().await,
+            genesis_hash: self.ledger.get_genesis_hash().await.into(),
+        };
+        if !request.chain_id.is_empty()
+            && (request.chain_id != reply.chain_id || request.genesis_hash != reply.genesis_hash)
+        {
+            return Err(GulfStreamError::NetworkMismatch.into());
+        }
+        return Ok(Response::new(reply));
+    }
+
+    async fn get_block_by_hash(
+        &self,
+        request: Request<GetBlockByHashRequest>,
+    ) -> Result<Response<GetBlockByHashResponse>, Status> {
+        let blockhash = Blockhash(request.into_inner().blockhash);
+        let block = self
+            .ledger
+            .state
+            .lock()
+            .await
+            .get_block(&blockhash)
+            .ok_or(GulfStreamError::BlockNotFound)
+            .map_err(|err| Status::not_found(err))?;
+        let reply = GetBlockByHashResponse {
+            block: Some(block.into()),
+        };
+        return Ok(Response::new(reply));
+    }
+
+    async fn get_transaction(
+        &self,
+        request: Request<GetTransactionRequest>,
+    ) -> Result<Response<GetTransactionResponse>, Status> {
+        let signature = Signature::deserialize(&mut &request.into_inner().signature[..])
+            .map_err(GulfStreamError::map_to_status)?;
+        let (tx, status) = self
+            .ledger
+            .get_transaction(&signature)
+            .await
+            .map_err(GulfStreamError::map_to_status)?
+            .ok_or(GulfStreamError::TxNotFound)
+            .map_err(Status::not_found)?;
+        let mut reply = GetTransactionResponse {
+            tx: Some(tx.into()),
+            ..Default::default()
+        };
+        match status {
+            TransactionStatus::Pending => reply.set_state(State::Pending),
+            TransactionStatus::Confirmed {
+                blockhash,
+                index,
+                confirmations,
+            } => {
+                reply.set_state(State::Success);
+                reply.blockhash = blockhash.0;
+                reply.block_index = index;
+                reply.confirmations = confirmations;
+            }
+            TransactionStatus::Dropped(reason) => {
+                reply.set_state(State::Fail);
+                reply.reason = reason;
+            }
+        }
+        return Ok(Response::new(reply));
+    }
+
     async fn send_transaction(
         &self,
         request: Request<SendTransactionRequest>,
     ) -> Result<Response<GenericResponse>, Status> {
+        let request = request.into_inner();
         let tx: Transaction = request
-            .into_inner()
             .tx
             .ok_or(GulfStreamError::Generic("Empty Tx".into()))
             .map_err(GulfStreamError::map_to_status)?
             .try_into()
             .map_err(GulfStreamError::map_to_status)?;
 
-        if !(tx.sign_is_valid() && tx.tx_msg_is_valid()) {
+        // Gossip comes back through other peers, acknowledge it without relaying it again.
+        if self.ledger.seen_txs.lock().await.contains(&tx.signature) {
+            let reply = GenericResponse {
+                message: format!("Tx {:?} already seen", tx.signature),
+            };
+            return Ok(Response::new(reply));
+        }
+
+        let chain_id = self.ledger.get_chain_id().await;
+        if !(tx.sign_is_valid(&chain_id) && tx.tx_msg_is_valid()) {
             return Err(GulfStreamError::TxIsNotValid.into());
         }
 
+        if !tx.window_is_valid() {
+            return Err(GulfStreamError::InvalidValidityWindow.into());
+        }
+
+        if let Err(err) = tx.get_cost() {
+            return Err(err.into());
+        }
+
+        if tx.is_coinbase() {
+            return Err(GulfStreamError::InvalidCoinbase.into());
+        }
+
+        let (latest_link, can_mint) = {
+            let state_guard = self.ledger.state.lock().await;
+            (
+                state_guard.get_latest(),
+                state_guard.get_policy().can_mint(&tx.payer),
+            )
+        };
+        if matches!(tx.msg, TransactionMessage::Mint { .. }) && !can_mint {
+            return Err(GulfStreamError::UnauthorizedMint.into());
+        }
+
+        let next_index = latest_link.block.index + 1;
+        if tx.is_expired_at(next_index)
+            || tx.valid_from > next_index + Transaction::MAX_VALIDITY_WINDOW
+        {
+            return Err(GulfStreamError::TxOutsideValidityWindow.into());
+        }
+
+        if tx.nonce < latest_link.get_nonce(&tx.payer) {
+            return Err(GulfStreamError::TxNonceAlreadyUsed.into());
+        }
+
         let reply = GenericResponse {
             message: format!("Tx {:?} inserted", tx.signature),
         };
 
-        if let Err(err) = self.ledger.db.insert_tx(&tx).await {
+        // Only pool what was persisted, so a failed write leaves nothing to gossip. The pool is
+        // not locked during the write, `insert` checks again for what changed meanwhile.
+        if let Err(err) = self.ledger.mem_pool.lock().await.check_insert(&tx) {
             return Err(err.into());
         }
+        if let Err(err) = self.ledger.store.insert_tx(&tx).await {
+            return Err(err.into());
+        }
+        if let Err(err) = self.ledger.mem_pool.lock().await.insert(tx.clone()) {
+            return Err(err.into());
+        }
+
+        self.ledger
+            .publish(LedgerEvent::TransactionAdmitted(Box::new(tx.clone())));
 
-        self.ledger.mem_pool.lock().await.push(tx);
+        if self
+            .ledger
+            .seen_txs
+            .lock()
+            .await
+            .insert(tx.signature.clone())
+        {
+            self.ledger.clone().gossip_transaction(tx, request.sender);
+        }
 
         return Ok(Response::new(reply));
     }
 
     async fn get_history(
         &self,
-        _request: Request<GetHistoryRequest>,
+        request: Request<GetHistoryRequest>,
     ) -> Result<Response<TransactionHistory>, Status> {
-        let history = self.ledger.get_transaction_history().await;
+        let request = request.into_inner();
+        let query = HistoryQuery {
+            address: if request.address.is_empty() {
+                None
+            } else {
+                Some(
+                    PublicKey::deserialize(&mut &request.address[..])
+                        .map_err(GulfStreamError::map_to_status)?,
+                )
+            },
+            from_index: request.from_index,
+            to_index: if request.to_index == 0 {
+                u64::MAX
+            } else {
+                request.to_index
+            },
+            direction: match request.direction() {
+                HistoryDirection::Any => Direction::Any,
+                HistoryDirection::Sent => Direction::Sent,
+                HistoryDirection::Received => Direction::Received,
+            },
+            cursor: if request.cursor.is_empty() {
+                None
+            } else {
+                Some(
+                    HistoryCursor::deserialize(&mut &request.cursor[..])
+                        .map_err(GulfStreamError::map_to_status)?,
+                )
+            },
+            limit: if request.limit == 0 {
+                HistoryQuery::DEFAULT_LIMIT
+            } else {
+                request.limit as usize
+            },
+        };
+        let page = self
+            .ledger
+            .get_transaction_history(&query)
+            .await
+            .map_err(GulfStreamError::map_to_status)?;
         let reply = TransactionHistory {
-            transactions: history
+            transactions: page
+                .entries
                 .into_iter()
-                .map(TryInto::try_into)
-                .collect::<Result<_, _>>()
-                .map_err(|_| {
-                    GulfStreamError::map_to_status(GulfStreamError::Generic(
-                        "Failed to get history".into(),
-                    ))
-                })?,
+                .map(|entry| entry.tx.into())
+                .collect(),
+            next_cursor: page
+                .next_cursor
+                .map(|cursor| cursor.serialize())
+                .unwrap_or_default(),
         };
         return Ok(Response::new(reply));
     }
@@ -118,44 +512,427 @@ impl Node for GulfStreamRpc {
         &self,
         request: Request<GetBalanceRequest>,
     ) -> Result<Response<GetBalanceResponse>, Status> {
-        let balance = self.ledger.state.lock().await.get_latest().get_balance(
-            &PublicKey::deserialize(&mut &request.into_inner().address[..])
-                .map_err(GulfStreamError::map_to_status)?,
-        );
+        let pk = PublicKey::deserialize(&mut &request.into_inner().address[..])
+            .map_err(GulfStreamError::map_to_status)?;
+        let latest_link = self.ledger.state.lock().await.get_latest();
         let reply = GetBalanceResponse {
-            balance: balance
+            balance: latest_link
+                .get_balance(&pk)
                 .to_u64()
                 .ok_or(GulfStreamError::Generic("Balance Negative".into()))
                 .map_err(GulfStreamError::map_to_status)?,
+            nonce: latest_link.get_nonce(&pk),
         };
         return Ok(Response::new(reply));
     }
 }
 
-#[tonic::async_trait]
-impl Broadcaster for Ledger {
-    async fn broadcast(&self, request: SendBlockRequest) -> Result<(), Vec<Endpoint>> {
-        let nodes = self.other_nodes.lock().await.to_owned();
-        let mut failed_request = vec![];
-        for endpoint in nodes.iter() {
-            if let Ok(mut client) = NodeClient::connect(endpoint.to_owned()).await {
-                if let Err(_) = client
-                    .send_block(tonic::Request::new(request.to_owned()))
-                    .await
-                {
-                    failed_request.push(endpoint.to_owned())
+/// Message fanned out to the peers.
+#[derive(Clone)]
+pub enum Gossip {
+    Block(SendBlockRequest),
+    Transaction(SendTransactionRequest),
+}
+
+impl Ledger {
+    /// Time a peer has to answer a broadcast message.
+    pub const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+    /// Further attempts for the peers a message could not reach.
+    pub const BROADCAST_RETRIES: u32 = 3;
+    /// Wait before the first retry, doubled before each following one.
+    pub const BROADCAST_BASE_BACKOFF: Duration = Duration::from_millis(100);
+
+    /// Sends `gossip` to all `endpoints` at once, then again with backoff to those that
+    /// could not be reached. Returns the endpoints still unreached after the last retry.
+    pub async fn deliver(
+        &self,
+        gossip: Gossip,
+        endpoints: Vec<Endpoint>,
+    ) -> Result<(), Vec<Endpoint>> {
+        let mut reached = vec![];
+        let mut pending = endpoints;
+        let mut backoff = Self::BROADCAST_BASE_BACKOFF;
+        for attempt in 0..=Self::BROADCAST_RETRIES {
+            if pending.is_empty() {
+                break;
+            }
+            if attempt > 0 {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                self.delivery.record_retried(pending.len() as u64);
+            }
+            let results = join_all(
+                pending
+                    .iter()
+                    .map(|endpoint| self.send_gossip(endpoint, &gossip)),
+            )
+            .await;
+            let mut unreached = vec![];
+            for (endpoint, result) in pending.into_iter().zip(results) {
+                match result {
+                    Ok(()) => reached.push(endpoint),
+                    Err(_) => unreached.push(endpoint),
                 }
             }
+            pending = unreached;
         }
-        if failed_request.is_empty() {
+
+        self.delivery.record_failed(pending.len() as u64);
+        let now = unix_timestamp();
+        let mut peers_guard = self.peers.lock().await;
+        reached
+            .iter()
+            .for_each(|endpoint| peers_guard.report_success(&PeerBook::get_uri(endpoint)));
+        pending
+            .iter()
+            .for_each(|endpoint| peers_guard.report_failure(&PeerBook::get_uri(endpoint), now));
+        if pending.is_empty() {
             Ok(())
         } else {
-            Err(failed_request)
+            Err(pending)
         }
     }
+
+    /// Whether a status comes from the way to the peer rather than from the peer handling
+    /// the message, so that the message may not have reached it.
+    fn is_transport_failure(status: &Status) -> bool {
+        matches!(
+            status.code(),
+            Code::Unavailable | Code::DeadlineExceeded | Code::Cancelled | Code::Unknown
+        )
+    }
+
+    /// Fails only if the peer could not be reached in time. A peer refusing the message
+    /// received it, there is nothing to retry.
+    async fn send_gossip(
+        &self,
+        endpoint: &Endpoint,
+        gossip: &Gossip,
+    ) -> Result<(), GulfStreamError> {
+        let mut client = self.connections.get(endpoint).await?;
+        let response = tokio::time::timeout(Self::PEER_TIMEOUT, async {
+            match gossip.clone() {
+                Gossip::Block(request) => client.send_block(request).await.map(|_| ()),
+                Gossip::Transaction(request) => client.send_transaction(request).await.map(|_| ()),
+            }
+        })
+        .await;
+        match response {
+            Ok(Ok(())) => {
+                self.delivery.record_delivered();
+                Ok(())
+            }
+            Ok(Err(status)) if !Self::is_transport_failure(&status) => {
+                self.delivery.record_rejected();
+                Ok(())
+            }
+            Ok(Err(status)) => {
+                self.connections.remove(endpoint).await;
+                Err(GulfStreamError::map(status.message()))
+            }
+            Err(_) => {
+                self.connections.remove(endpoint).await;
+                Err(GulfStreamError::map("Peer timed out"))
+            }
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Broadcaster for Ledger {
+    async fn broadcast(&self, request: SendBlockRequest) -> Result<(), Vec<Endpoint>> {
+        let nodes = self.peers.lock().await.get_active(unix_timestamp());
+        self.deliver(Gossip::Block(request), nodes).await
+    }
+
+    async fn broadcast_transaction(
+        &self,
+        request: SendTransactionRequest,
+        except: &str,
+    ) -> Result<(), Vec<Endpoint>> {
+        let except = PeerBook::parse_endpoint(except)
+            .map(|endpoint| PeerBook::get_uri(&endpoint))
+            .unwrap_or_default();
+        let nodes = self
+            .peers
+            .lock()
+            .await
+            .get_active(unix_timestamp())
+            .into_iter()
+            .filter(|endpoint| PeerBook::get_uri(endpoint).ne(&except))
+            .collect();
+        self.deliver(Gossip::Transaction(request), nodes).await
+    }
 }
 
 #[tonic::async_trait]
 pub trait Broadcaster {
     async fn broadcast(&self, request: SendBlockRequest) -> Result<(), Vec<Endpoint>>;
+
+    /// Sends the transaction to every peer but `except`, the endpoint it came from.
+    async fn broadcast_transaction(
+        &self,
+        request: SendTransactionRequest,
+        except: &str,
+    ) -> Result<(), Vec<Endpoint>>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ledger::ledger::BlockBuilder;
+    use crate::mempool::mempool::Mempool;
+    use crate::peer::{metrics::DeliveryMetrics, pool::ConnectionPool};
+    use crate::state::blockchain::Blockchain;
+    use crate::store::memory::MemoryStore;
+    use crate::utils::{fixtures::*, seen::SeenSet};
+    use tokio::sync::Mutex;
+
+    fn ledger(peers: PeerBook) -> Ledger {
+        Ledger {
+            state: Mutex::new(Blockchain::default()),
+            mem_pool: Mutex::new(Mempool::default()),
+            peers: Mutex::new(peers),
+            store: Arc::new(MemoryStore::default()),
+            endpoint: None,
+            seen_txs: Mutex::new(SeenSet::new(1)),
+            connections: ConnectionPool::default(),
+            delivery: DeliveryMetrics::default(),
+            events: Ledger::new_events(),
+            miner: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_unreachable_peer_after_retries() {
+        let unreachable = PeerBook::parse_endpoint("127.0.0.1:1").unwrap();
+        let mut peers = PeerBook::default();
+        peers.add(unreachable.clone(), 0);
+        let ledger = ledger(peers);
+
+        let request = SendBlockRequest {
+            block: Some(Block::genesis().into()),
+            sender: String::new(),
+        };
+        let failed = ledger.broadcast(request).await.unwrap_err();
+
+        assert_eq!(failed.len(), 1);
+        assert_eq!(
+            PeerBook::get_uri(&failed[0]),
+            PeerBook::get_uri(&unreachable)
+        );
+        assert_eq!(
+            ledger.delivery.get_retried(),
+            Ledger::BROADCAST_RETRIES as u64
+        );
+        assert_eq!(ledger.delivery.get_failed(), 1);
+        assert!(ledger
+            .peers
+            .lock()
+            .await
+            .get_active(unix_timestamp())
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuses_peer_from_other_network() {
+        let rpc = GulfStreamRpc {
+            ledger: Arc::new(ledger(PeerBook::default())),
+        };
+        let request = |genesis_hash: Blockhash| {
+            Request::new(AnnouncePeerRequest {
+                endpoint: "127.0.0.1:50052".into(),
+                genesis_hash: genesis_hash.into(),
+            })
+        };
+
+        let other = Blockhash(vec![0; 32]);
+        let err = rpc.announce_peer(request(other)).await.unwrap_err();
+        assert_eq!(err.code(), Code::FailedPrecondition);
+        assert!(rpc.ledger.peers.lock().await.is_empty());
+
+        let genesis_hash = rpc.ledger.get_genesis_hash().await;
+        rpc.announce_peer(request(genesis_hash)).await.unwrap();
+        assert!(rpc.ledger.peers.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn adds_announced_peer_once_reached() {
+        let socket = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        Arc::new(ledger(PeerBook::default())).run_rpc(socket);
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+        let rpc = GulfStreamRpc {
+            ledger: Arc::new(ledger(PeerBook::default())),
+        };
+        let request = AnnouncePeerRequest {
+            endpoint: socket.to_string(),
+            genesis_hash: rpc.ledger.get_genesis_hash().await.into(),
+        };
+        rpc.announce_peer(Request::new(request)).await.unwrap();
+
+        let uri = PeerBook::get_uri(&PeerBook::parse_endpoint(&socket.to_string()).unwrap());
+        assert!(rpc.ledger.peers.lock().await.contains(&uri));
+    }
+
+    #[tokio::test]
+    async fn handshakes_on_same_network_only() {
+        let rpc = GulfStreamRpc {
+            ledger: Arc::new(ledger(PeerBook::default())),
+        };
+
+        let network = rpc
+            .handshake(Request::new(HandshakeRequest::default()))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(network.chain_id, rpc.ledger.get_chain_id().await);
+
+        let same = HandshakeRequest {
+            chain_id: network.chain_id.clone(),
+            genesis_hash: network.genesis_hash.clone(),
+        };
+        assert!(rpc.handshake(Request::new(same)).await.is_ok());
+
+        let other = HandshakeRequest {
+            chain_id: "other".into(),
+            genesis_hash: network.genesis_hash,
+        };
+        let err = rpc.handshake(Request::new(other)).await.unwrap_err();
+        assert_eq!(err.code(), Code::FailedPrecondition);
+    }
+
+    #[tokio::test]
+    async fn streams_admitted_transactions() {
+        let rpc = GulfStreamRpc {
+            ledger: Arc::new(ledger(PeerBook::default())),
+        };
+        let mut stream = rpc
+            .subscribe_transactions(Request::new(SubscribeTransactionsRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+
+        rpc.ledger
+            .publish(LedgerEvent::TransactionAdmitted(Box::new(unsigned(
+                &PublicKey::default(),
+                0,
+                1,
+            ))));
+
+        let event = stream.next().await.unwrap().unwrap();
+        assert_eq!(event.state, State::Pending as i32);
+        assert_eq!(event.tx.unwrap().nonce, 0);
+    }
+
+    #[test]
+    fn retries_transport_failures_only() {
+        assert!(Ledger::is_transport_failure(&Status::unavailable("")));
+        assert!(Ledger::is_transport_failure(&Status::deadline_exceeded("")));
+        assert!(!Ledger::is_transport_failure(
+            &GulfStreamError::TxAlreadyInMempool.into()
+        ));
+        assert!(!Ledger::is_transport_failure(&Status::not_found("")));
+    }
+
+    #[tokio::test]
+    async fn ends_subscription_on_error() {
+        let ledger = ledger(PeerBook::default());
+        let mut stream = subscription(ledger.subscribe(), |_| {
+            Err::<Vec<()>, _>(GulfStreamError::map("Balance Negative"))
+        });
+
+        ledger.publish(LedgerEvent::TransactionAdmitted(Box::new(unsigned(
+            &PublicKey::default(),
+            0,
+            1,
+        ))));
+        ledger.publish(LedgerEvent::TransactionAdmitted(Box::new(unsigned(
+            &PublicKey::default(),
+            1,
+            1,
+        ))));
+
+        assert_eq!(
+            stream.next().await.unwrap().unwrap_err().code(),
+            Code::Aborted
+        );
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn ends_lagging_subscription() {
+        let ledger = ledger(PeerBook::default());
+        let mut stream = subscription(ledger.subscribe(), |_| Ok(vec![()]));
+
+        (0..=Ledger::EVENTS_CAPACITY as u64).for_each(|nonce| {
+            ledger.publish(LedgerEvent::TransactionAdmitted(Box::new(unsigned(
+                &PublicKey::default(),
+                nonce,
+                1,
+            ))))
+        });
+
+        assert_eq!(
+            stream.next().await.unwrap().unwrap_err().code(),
+            Code::ResourceExhausted
+        );
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn looks_up_transaction_state() {
+        let rpc = GulfStreamRpc {
+            ledger: Arc::new(ledger(PeerBook::default())),
+        };
+        let tx = unsigned(&PublicKey::default(), 0, 1);
+        let request = || {
+            Request::new(GetTransactionRequest {
+                signature: tx.signature.serialize(),
+            })
+        };
+
+        let err = rpc.get_transaction(request()).await.unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+
+        rpc.ledger.store.insert_tx(&tx).await.unwrap();
+        let reply = rpc.get_transaction(request()).await.unwrap().into_inner();
+        assert_eq!(reply.state(), State::Fail);
+        assert!(!reply.reason.is_empty());
+
+        rpc.ledger.mem_pool.lock().await.insert(tx.clone()).unwrap();
+        let reply = rpc.get_transaction(request()).await.unwrap().into_inner();
+        assert_eq!(reply.state(), State::Pending);
+        assert!(reply.blockhash.is_empty());
+    }
+
+    #[tokio::test]
+    async fn mines_reward_without_traffic() {
+        let ledger = Ledger {
+            miner: Some(keypair()),
+            ..ledger(PeerBook::default())
+        };
+        let genesis = ledger.state.lock().await.get_latest();
+        let block = ledger.try_build_block(genesis).await.unwrap();
+
+        assert_eq!(block.transactions.len(), 1);
+        assert!(block.transactions[0].is_coinbase());
+        assert_eq!(ledger.state.lock().await.get_latest().block, block);
+        let (_, status) = ledger
+            .get_transaction(&block.transactions[0].signature)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            status,
+            TransactionStatus::Confirmed {
+                blockhash: block.blockhash.clone(),
+                index: 1,
+                confirmations: 1,
+            }
+        );
+    }
 }
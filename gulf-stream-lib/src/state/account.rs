@@ -0,0 +1,137 @@
+use super::{block::Block, link::Link, transaction::BalanceDelta};
+use crate::{
+    ed25519::publickey::PublicKey,
+    err::GulfStreamError,
+    utils::serde::{BytesDeserialize, BytesSerialize},
+};
+use std::{collections::HashMap, sync::Arc};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Account {
+    pub balance: BalanceDelta,
+    /// Number of transactions the account paid for.
+    pub nonce: u64,
+}
+
+impl BytesSerialize for Account {
+    fn serialize(&self) -> Vec<u8> {
+        let mut vec = self.balance.serialize();
+        vec.extend(self.nonce.serialize());
+        vec
+    }
+}
+
+impl BytesDeserialize for Account {
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, GulfStreamError> {
+        Ok(Self {
+            balance: BalanceDelta::deserialize(buf)?,
+            nonce: u64::deserialize(buf)?,
+        })
+    }
+}
+
+/// Accounts touched by a block, layered on top of the state of its parent. Every
+/// `CHECKPOINT_INTERVAL` blocks the layer also keeps the whole state, so a lookup walks back
+/// at most that many links whatever the length of the chain.
+#[derive(Debug, Default)]
+pub struct AccountState {
+    diff: HashMap<PublicKey, Account>,
+    checkpoint: Option<Arc<HashMap<PublicKey, Account>>>,
+}
+
+impl AccountState {
+    pub const CHECKPOINT_INTERVAL: u64 = 64;
+
+    pub fn new(parent: Option<&Link>, block: &Block) -> Result<Self, GulfStreamError> {
+        let get_parent_account =
+            |pk: &PublicKey| parent.map(|link| link.get_account(pk)).unwrap_or_default();
+        let mut diff: HashMap<PublicKey, Account> = HashMap::new();
+        for tx in block.transactions.iter() {
+            for (pk, delta) in tx.get_balance_deltas()? {
+                let account = diff
+                    .entry(pk.clone())
+                    .or_insert_with(|| get_parent_account(&pk));
+                account.balance = account.balance.checked_add(delta)?;
+            }
+            if !tx.is_coinbase() {
+                diff.entry(tx.payer.clone())
+                    .or_insert_with(|| get_parent_account(&tx.payer))
+                    .nonce += 1;
+            }
+        }
+
+        let checkpoint = if block.index % Self::CHECKPOINT_INTERVAL == 0 {
+            let mut accounts = parent.map(Link::get_accounts).unwrap_or_default();
+            accounts.extend(diff.clone());
+            Some(Arc::new(accounts))
+        } else {
+            None
+        };
+
+        Ok(Self { diff, checkpoint })
+    }
+
+    pub fn get(&self, pk: &PublicKey) -> Option<Account> {
+        match &self.checkpoint {
+            Some(accounts) => Some(accounts.get(pk).copied().unwrap_or_default()),
+            None => self.diff.get(pk).copied(),
+        }
+    }
+
+    pub fn get_checkpoint(&self) -> Option<Arc<HashMap<PublicKey, Account>>> {
+        self.checkpoint.clone()
+    }
+
+    pub fn get_diff(&self) -> &HashMap<PublicKey, Account> {
+        &self.diff
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::{amount::Amount, transaction::Transaction};
+    use crate::utils::fixtures::unsigned;
+
+    fn extend(link: &Arc<Link>, transactions: Vec<Transaction>) -> Arc<Link> {
+        let block = Block::create_block(
+            link.block.index + 1,
+            &link.block.blockhash,
+            transactions,
+            0,
+            0,
+            0,
+        );
+        link.clone().try_insert(&block).unwrap()
+    }
+
+    #[test]
+    fn reads_across_checkpoints() {
+        let (pk1, pk2) = (PublicKey::random(), PublicKey::random());
+        let genesis = Arc::new(Link::default());
+        let mut link = extend(&genesis, vec![unsigned(&pk2, 0, 5)]);
+        for nonce in 0..(2 * AccountState::CHECKPOINT_INTERVAL + 3) {
+            link = extend(&link, vec![unsigned(&pk1, nonce, 1)]);
+        }
+
+        let expected = 2 * AccountState::CHECKPOINT_INTERVAL + 3;
+        assert_eq!(link.get_balance(&pk1), BalanceDelta::Pos(Amount(expected)));
+        assert_eq!(link.get_nonce(&pk1), expected);
+        assert_eq!(link.get_balance(&pk2), BalanceDelta::Pos(Amount(5)));
+        assert_eq!(link.get_nonce(&pk2), 1);
+        assert_eq!(link.get_account(&PublicKey::random()), Account::default());
+    }
+
+    #[test]
+    fn reads_forks_independently() {
+        let pk = PublicKey::random();
+        let genesis = Arc::new(Link::default());
+        let base = extend(&genesis, vec![unsigned(&pk, 0, 5)]);
+        let fork_a = extend(&base, vec![unsigned(&pk, 1, 1)]);
+        let fork_b = extend(&base, vec![unsigned(&pk, 1, 2)]);
+
+        assert_eq!(base.get_balance(&pk), BalanceDelta::Pos(Amount(5)));
+        assert_eq!(fork_a.get_balance(&pk), BalanceDelta::Pos(Amount(6)));
+        assert_eq!(fork_b.get_balance(&pk), BalanceDelta::Pos(Amount(7)));
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt::Display;
+
+use crate::{
+    err::GulfStreamError,
+    utils::serde::{BytesDeserialize, BytesSerialize},
+};
+
+/// Number of coins. Arithmetic on it is checked : going out of range is an error, never a
+/// panic or a wrapped balance.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(pub u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    pub fn checked_add(self, rhs: Amount) -> Result<Amount, GulfStreamError> {
+        self.0
+            .checked_add(rhs.0)
+            .map(Amount)
+            .ok_or(GulfStreamError::AmountOverflow)
+    }
+
+    pub fn checked_sub(self, rhs: Amount) -> Result<Amount, GulfStreamError> {
+        self.0
+            .checked_sub(rhs.0)
+            .map(Amount)
+            .ok_or(GulfStreamError::AmountOverflow)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Amount> for u64 {
+    fn from(value: Amount) -> Self {
+        value.0
+    }
+}
+
+impl Display for Amount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl BytesSerialize for Amount {
+    fn serialize(&self) -> Vec<u8> {
+        self.0.serialize()
+    }
+}
+
+impl BytesDeserialize for Amount {
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, GulfStreamError> {
+        Ok(Self(u64::deserialize(buf)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn checks_overflow() {
+        assert_eq!(Amount(1).checked_add(Amount(2)), Ok(Amount(3)));
+        assert_eq!(
+            Amount(u64::MAX).checked_add(Amount(1)),
+            Err(GulfStreamError::AmountOverflow)
+        );
+        assert_eq!(Amount(3).checked_sub(Amount(2)), Ok(Amount(1)));
+        assert_eq!(
+            Amount(2).checked_sub(Amount(3)),
+            Err(GulfStreamError::AmountOverflow)
+        );
+    }
+}
@@ -1,9 +1,15 @@
 use std::collections::HashMap;
 
-use crate::{ed25519::publickey::PublicKey, utils::serde::BytesSerialize};
+use crate::{
+    ed25519::publickey::PublicKey,
+    err::GulfStreamError,
+    utils::serde::{BytesDeserialize, BytesSerialize},
+};
 
 use super::{
+    amount::Amount,
     blockhash::Blockhash,
+    genesis::Genesis,
     transaction::{BalanceDelta, Transaction},
 };
 
@@ -14,6 +20,10 @@ pub struct Block {
     pub previous_blockhash: Blockhash,
     pub transactions: Vec<Transaction>,
     pub nonce: u64,
+    /// Number of leading zero bits the blockhash must have.
+    pub difficulty: u32,
+    /// Seconds since the unix epoch.
+    pub timestamp: u64,
 }
 
 impl Block {
@@ -22,13 +32,24 @@ impl Block {
         previous_blockhash: &Blockhash,
         transactions: Vec<Transaction>,
         nonce: u64,
+        difficulty: u32,
+        timestamp: u64,
     ) -> Self {
         Self {
             index,
-            blockhash: Blockhash::from_data(index, previous_blockhash, &transactions, nonce),
+            blockhash: Blockhash::from_data(
+                index,
+                previous_blockhash,
+                &transactions,
+                nonce,
+                difficulty,
+                timestamp,
+            ),
             transactions,
             previous_blockhash: previous_blockhash.to_owned(),
             nonce,
+            difficulty,
+            timestamp,
         }
     }
 
@@ -38,26 +59,69 @@ impl Block {
             &self.previous_blockhash,
             &self.transactions,
             self.nonce,
+            self.difficulty,
+            self.timestamp,
         )
     }
 
+    /// Genesis block of the default network.
     pub fn genesis() -> Self {
-        let previous_blockhash = Blockhash("genesis".as_bytes().to_vec());
-        Self {
-            index: 0,
-            blockhash: Blockhash::from_data(0, &previous_blockhash, &vec![], 0),
-            transactions: vec![],
-            previous_blockhash,
-            nonce: 0,
-        }
+        Genesis::default().get_block()
+    }
+
+    /// Work this block adds to its chain : the expected number of hashes needed to meet
+    /// its difficulty.
+    pub fn work(&self) -> u128 {
+        1u128.checked_shl(self.difficulty).unwrap_or(u128::MAX)
+    }
+
+    /// Gas paid by the transactions of this block, which its coinbase may claim.
+    pub fn get_fees(&self) -> Result<Amount, GulfStreamError> {
+        Self::get_fees_from_txs(&self.transactions)
     }
 
-    pub fn get_balance_deltas(&self) -> HashMap<PublicKey, BalanceDelta> {
-        let mut res = HashMap::new();
-        self.transactions.iter().for_each(|tx: &Transaction| {
-            BalanceDelta::update_table(tx, &mut res);
-        });
-        return res;
+    pub fn get_fees_from_txs(transactions: &[Transaction]) -> Result<Amount, GulfStreamError> {
+        transactions
+            .iter()
+            .filter(|tx| !tx.is_coinbase())
+            .try_fold(Amount::ZERO, |fees, tx| fees.checked_add(Amount(tx.gas)))
+    }
+
+    /// The payers' gas shows up as a debit here and, since validation requires the coinbase
+    /// to claim the fees, as a credit to the producer.
+    pub fn get_balance_deltas(&self) -> Result<HashMap<PublicKey, BalanceDelta>, GulfStreamError> {
+        Transaction::get_balance_deltas_from_txs(&self.transactions)
+    }
+}
+
+impl BytesSerialize for Block {
+    fn serialize(&self) -> Vec<u8> {
+        let mut vec = vec![];
+        vec.extend(self.index.serialize());
+        vec.extend(self.blockhash.0.serialize());
+        vec.extend(self.previous_blockhash.0.serialize());
+        vec.extend(self.nonce.serialize());
+        vec.extend(self.difficulty.serialize());
+        vec.extend(self.timestamp.serialize());
+        vec.extend(Transaction::get_raw_txs(&self.transactions));
+        vec
+    }
+}
+
+impl BytesDeserialize for Block {
+    /// Transactions take the rest of `buf`.
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, GulfStreamError> {
+        let block = Self {
+            index: u64::deserialize(buf)?,
+            blockhash: Blockhash(Vec::deserialize(buf)?),
+            previous_blockhash: Blockhash(Vec::deserialize(buf)?),
+            nonce: u64::deserialize(buf)?,
+            difficulty: u32::deserialize(buf)?,
+            timestamp: u64::deserialize(buf)?,
+            transactions: Transaction::from_raw_txs(buf)?,
+        };
+        *buf = &[];
+        Ok(block)
     }
 }
 
@@ -1,48 +1,174 @@
-use super::{block::Block, link::Link};
-use crate::err::*;
-use std::{sync::Arc, vec};
+use super::{
+    block::Block,
+    blockhash::Blockhash,
+    difficulty::{next_difficulty, MAX_ADJUSTMENT, MIN_DIFFICULTY, RETARGET_INTERVAL},
+    genesis::Genesis,
+    link::Link,
+    orphan::OrphanPool,
+    policy::MonetaryPolicy,
+    transaction::Transaction,
+};
+use crate::{err::*, utils::time::unix_timestamp};
+use std::{collections::HashMap, sync::Arc, vec};
 
 pub struct Blockchain {
     genesis: Arc<Link>,
+    /// Every link of the tree, forks included.
+    links: HashMap<Blockhash, Arc<Link>>,
     latest_links: Vec<Arc<Link>>,
     heighest_link: Arc<Link>,
+    orphans: OrphanPool,
+    policy: MonetaryPolicy,
+    chain_id: String,
+}
+
+/// Blocks that joined or left the canonical chain after an insertion.
+/// `connected` goes from the fork point to the new head, `disconnected` from the old head
+/// down to the fork point.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ChainUpdate {
+    pub connected: Vec<Block>,
+    pub disconnected: Vec<Block>,
+}
+
+impl ChainUpdate {
+    pub fn is_reorg(&self) -> bool {
+        !self.disconnected.is_empty()
+    }
+
+    /// Transactions the canonical chain gained.
+    pub fn connected_transactions(&self) -> Vec<Transaction> {
+        self.connected
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// Transactions of the losing branch that the winning branch did not include.
+    pub fn orphaned_transactions(&self) -> Vec<Transaction> {
+        let connected_txs = self.connected_transactions();
+        self.disconnected
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .filter(|tx| {
+                !connected_txs
+                    .iter()
+                    .any(|connected_tx| connected_tx.signature.eq(&tx.signature))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Folds `next`, which happened after `self`, into `self`.
+    pub fn merge(&mut self, next: ChainUpdate) {
+        for block in next.disconnected {
+            match self.connected.last() {
+                Some(last) if last.blockhash.eq(&block.blockhash) => {
+                    self.connected.pop();
+                }
+                _ => self.disconnected.push(block),
+            }
+        }
+        self.connected.extend(next.connected);
+    }
 }
 
 impl Blockchain {
     pub const LASTEST_LINK_LENGTH: usize = 10;
+    /// Number of blocks whose median timestamp a new block must exceed.
+    pub const MEDIAN_TIME_SPAN: usize = 11;
+    /// How far ahead of the local clock a block timestamp may be, in seconds.
+    pub const MAX_FUTURE_BLOCK_TIME: u64 = 2 * 60 * 60;
+
+    /// A block whose parent is unknown goes to the orphan pool, to be inserted by
+    /// `connect_orphans` once the parent is, if its header holds up on its own.
+    pub fn try_insert(&mut self, block: &Block) -> Result<ChainUpdate, GulfStreamError> {
+        let previous_link = match self.try_find_previous_link(block) {
+            Err(GulfStreamError::DidNotFindPreviousBlock) => {
+                self.validate_orphan(block)?;
+                self.orphans.insert(block.clone());
+                return Err(GulfStreamError::DidNotFindPreviousBlock);
+            }
+            previous_link => previous_link?,
+        };
+        if previous_link
+            .next_blocks
+            .try_lock()?
+            .iter()
+            .any(|link| link.block.blockhash.eq(&block.blockhash))
+        {
+            return Err(GulfStreamError::BlockAlreadyKnown);
+        }
+        Self::validate(&self.chain_id, &self.policy, &previous_link, block)?;
+        let new_link = previous_link.try_insert(block)?;
+        self.links.insert(block.blockhash.clone(), new_link.clone());
+        self.update_latest(new_link.clone());
+        Ok(self.update_heighest(new_link))
+    }
+
+    /// Rebuilds a chain from stored blocks, in any order. `head` breaks the fork choice tie
+    /// the way it was broken when the blocks were first received.
+    pub fn restore(
+        genesis: &Genesis,
+        mut blocks: Vec<Block>,
+        head: Option<&Blockhash>,
+    ) -> Result<Self, GulfStreamError> {
+        let mut blockchain = Self::new(genesis)?;
+        blocks.sort_by_key(|block| block.index);
+        for block in blocks.iter().filter(|block| block.index > 0) {
+            blockchain.try_insert(block)?;
+        }
+        if let Some(head) = head {
+            blockchain.try_restore_head(head);
+        }
+        Ok(blockchain)
+    }
+
+    fn try_restore_head(&mut self, head: &Blockhash) {
+        let tie = self.latest_links.iter().find(|link| {
+            link.block.blockhash.eq(head) && link.chain_work == self.heighest_link.chain_work
+        });
+        if let Some(link) = tie {
+            self.heighest_link = link.clone();
+        }
+    }
 
-    pub fn try_insert(&mut self, block: &Block) -> Result<(), GulfStreamError> {
-        if self.is_valid(block) {
-            if let Some(new_link) = self.latest_links.iter().fold(None, |res, link| match res {
-                Some(_) => res,
-                None => {
-                    if let Ok(new_link) = link.clone().try_insert(block) {
-                        Some(new_link)
-                    } else {
-                        None
+    /// Inserts the orphans descending from `parent`, returns them in insertion order with
+    /// the resulting chain update. Invalid orphans are dropped.
+    pub fn connect_orphans(&mut self, parent: &Blockhash) -> (Vec<Block>, ChainUpdate) {
+        let mut connected_orphans = vec![];
+        let mut update = ChainUpdate::default();
+        let mut parents = vec![parent.clone()];
+        while let Some(parent) = parents.pop() {
+            for orphan in self.orphans.take_children(&parent) {
+                match self.try_insert(&orphan) {
+                    Ok(orphan_update) => {
+                        update.merge(orphan_update);
+                        parents.push(orphan.blockhash.clone());
+                        connected_orphans.push(orphan);
                     }
-                }
-            }) {
-                self.update_latest(new_link.clone());
-                self.update_heighest(new_link);
-                return Ok(());
-            } else {
-                if let Ok(previous_link) = self
-                    .genesis
-                    .clone()
-                    .try_find_block(&block.previous_blockhash, block.index - 1)
-                {
-                    let new_link = previous_link.try_insert(block)?;
-                    self.update_latest(new_link.clone());
-                    self.update_heighest(new_link);
-
-                    return Ok(());
-                } else {
-                    return Err(GulfStreamError::DidNotFindPreviousBlock);
+                    Err(GulfStreamError::BlockAlreadyKnown) => {
+                        parents.push(orphan.blockhash.clone());
+                    }
+                    Err(_) => {}
                 }
             }
-        } else {
-            Err(GulfStreamError::BlockIsNotValid)
+        }
+        (connected_orphans, update)
+    }
+
+    pub fn get_orphan_count(&self) -> usize {
+        self.orphans.len()
+    }
+
+    fn try_find_previous_link(&self, block: &Block) -> Result<Arc<Link>, GulfStreamError> {
+        if block.index == 0 {
+            return Err(GulfStreamError::WrongIndex);
+        }
+        match self.links.get(&block.previous_blockhash) {
+            Some(link) if link.block.index + 1 == block.index => Ok(link.clone()),
+            _ => Err(GulfStreamError::DidNotFindPreviousBlock),
         }
     }
 
@@ -53,28 +179,818 @@ impl Blockchain {
         self.latest_links.insert(0, new_link);
     }
 
-    fn update_heighest(&mut self, new_link: Arc<Link>) {
-        if self.heighest_link.block.index <= new_link.block.index {
-            self.heighest_link = new_link
+    /// Fork choice : the canonical head is the tip with the most cumulative work, the first
+    /// seen tip wins a tie.
+    fn update_heighest(&mut self, new_link: Arc<Link>) -> ChainUpdate {
+        if new_link.chain_work > self.heighest_link.chain_work {
+            let update = Self::reorganize(&self.heighest_link, &new_link);
+            self.heighest_link = new_link;
+            update
+        } else {
+            ChainUpdate::default()
+        }
+    }
+
+    fn reorganize(old_head: &Arc<Link>, new_head: &Arc<Link>) -> ChainUpdate {
+        let mut update = ChainUpdate::default();
+        let mut old_link = old_head.clone();
+        let mut new_link = new_head.clone();
+        while new_link.block.index > old_link.block.index {
+            update.connected.push(new_link.block.clone());
+            new_link = new_link.get_parent().unwrap_or(new_link);
+        }
+        while old_link.block.index > new_link.block.index {
+            update.disconnected.push(old_link.block.clone());
+            old_link = old_link.get_parent().unwrap_or(old_link);
+        }
+        while old_link.block.blockhash.ne(&new_link.block.blockhash) {
+            match (old_link.get_parent(), new_link.get_parent()) {
+                (Some(old_parent), Some(new_parent)) => {
+                    update.disconnected.push(old_link.block.clone());
+                    update.connected.push(new_link.block.clone());
+                    old_link = old_parent;
+                    new_link = new_parent;
+                }
+                _ => break,
+            }
         }
+        update.connected.reverse();
+        update
     }
 
-    fn is_valid(&self, _block: &Block) -> bool {
-        true
+    /// What can be checked without the parent : the blockhash and its proof of work, at a
+    /// difficulty the chain could have come down to by that index, and the timestamp.
+    fn validate_orphan(&self, block: &Block) -> Result<(), GulfStreamError> {
+        let head = &self.heighest_link.block;
+        if block.index == 0 || block.index > head.index + OrphanPool::MAX_DISTANCE {
+            return Err(GulfStreamError::WrongIndex);
+        }
+        if block.compute_blockhash().ne(&block.blockhash) {
+            return Err(GulfStreamError::InvalidBlockhash);
+        }
+        if block.timestamp > unix_timestamp() + Self::MAX_FUTURE_BLOCK_TIME {
+            return Err(GulfStreamError::BlockTooFarInFuture);
+        }
+        let retargets = block.index.saturating_sub(head.index) / RETARGET_INTERVAL + 1;
+        let min_difficulty = head
+            .difficulty
+            .saturating_sub(MAX_ADJUSTMENT.saturating_mul(retargets as u32))
+            .max(MIN_DIFFICULTY);
+        if block.difficulty < min_difficulty {
+            return Err(GulfStreamError::WrongDifficulty);
+        }
+        if !block.blockhash.is_valid(block.difficulty) {
+            return Err(GulfStreamError::InsufficientProofOfWork);
+        }
+        Ok(())
     }
 
+    /// Checks a block against the link it extends : header, proof of work, transactions,
+    /// monetary policy and balances once its transactions are replayed on top of the parent
+    /// state.
+    fn validate(
+        chain_id: &str,
+        policy: &MonetaryPolicy,
+        previous_link: &Arc<Link>,
+        block: &Block,
+    ) -> Result<(), GulfStreamError> {
+        if block.compute_blockhash().ne(&block.blockhash) {
+            return Err(GulfStreamError::InvalidBlockhash);
+        }
+        if block.timestamp <= Self::median_time_past(previous_link) {
+            return Err(GulfStreamError::BlockTooOld);
+        }
+        if block.timestamp > unix_timestamp() + Self::MAX_FUTURE_BLOCK_TIME {
+            return Err(GulfStreamError::BlockTooFarInFuture);
+        }
+        if block.difficulty != next_difficulty(previous_link) {
+            return Err(GulfStreamError::WrongDifficulty);
+        }
+        if !block.blockhash.is_valid(block.difficulty) {
+            return Err(GulfStreamError::InsufficientProofOfWork);
+        }
+        for tx in block.transactions.iter() {
+            if !tx.sign_is_valid(chain_id) {
+                return Err(GulfStreamError::InvalidTxSignature);
+            }
+            if !tx.tx_msg_is_valid() {
+                return Err(GulfStreamError::InvalidTxMessage);
+            }
+            if !tx.window_is_valid() {
+                return Err(GulfStreamError::InvalidValidityWindow);
+            }
+            if !tx.can_be_included_at(block.index) {
+                return Err(GulfStreamError::TxOutsideValidityWindow);
+            }
+            tx.get_cost()?;
+        }
+        policy.validate(block)?;
+        Self::validate_nonces(previous_link, &block.transactions)?;
+        Self::validate_balances(previous_link, &block.transactions)
+    }
+
+    /// Each payer's transactions must carry consecutive nonces, starting from its count of
+    /// transactions already on chain.
+    fn validate_nonces(previous_link: &Link, txs: &[Transaction]) -> Result<(), GulfStreamError> {
+        let payers = txs.iter().map(|tx| tx.payer.clone()).collect();
+        let mut nonces = previous_link.get_nonces(&payers);
+        for tx in txs.iter().filter(|tx| !tx.is_coinbase()) {
+            let nonce = nonces.entry(tx.payer.clone()).or_default();
+            if tx.nonce != *nonce {
+                return Err(GulfStreamError::WrongTxNonce);
+            }
+            *nonce += 1;
+        }
+        Ok(())
+    }
+
+    /// Lower bound, exclusive, of the timestamp of a child of `previous_link`.
+    pub fn median_time_past(previous_link: &Arc<Link>) -> u64 {
+        previous_link
+            .clone()
+            .median_time_past(Self::MEDIAN_TIME_SPAN)
+    }
+
+    fn validate_balances(
+        previous_link: &Link,
+        txs: &Vec<Transaction>,
+    ) -> Result<(), GulfStreamError> {
+        let mut balances = previous_link.get_balances(&Transaction::get_involved_pk_from_txs(txs));
+        for tx in txs.iter() {
+            for (pk, delta) in tx.get_balance_deltas()? {
+                let balance = balances.entry(pk).or_default();
+                *balance = balance.checked_add(delta)?;
+                if !balance.is_positive_or_nil() {
+                    return Err(GulfStreamError::Overspending);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Any block of the tree, forks included.
+    pub fn get_block(&self, blockhash: &Blockhash) -> Option<Block> {
+        self.links.get(blockhash).map(|link| link.block.clone())
+    }
+
+    /// Up to `count` blocks of the canonical chain, starting at `from_index`.
+    pub fn get_blocks(&self, from_index: u64, count: u64) -> Vec<Block> {
+        let head_index = self.heighest_link.block.index;
+        if count == 0 || from_index > head_index {
+            return vec![];
+        }
+        let to_index = head_index.min(from_index.saturating_add(count - 1));
+        let mut blocks = vec![];
+        let mut current_link = self.heighest_link.clone().get_ancestor(to_index);
+        while let Some(link) = current_link {
+            if link.block.index < from_index {
+                break;
+            }
+            blocks.push(link.block.clone());
+            current_link = link.get_parent();
+        }
+        blocks.reverse();
+        blocks
+    }
+
+    /// Head of the canonical chain.
     pub fn get_latest(&self) -> Arc<Link> {
-        self.latest_links[0].clone()
+        self.heighest_link.clone()
+    }
+}
+
+impl Blockchain {
+    pub fn new(genesis: &Genesis) -> Result<Self, GulfStreamError> {
+        let root = Arc::new(Link::new_root(genesis.get_block())?);
+        Ok(Self {
+            links: HashMap::from([(root.block.blockhash.clone(), root.clone())]),
+            latest_links: vec![root.clone()],
+            heighest_link: root.clone(),
+            genesis: root,
+            orphans: OrphanPool::default(),
+            policy: genesis.policy.clone(),
+            chain_id: genesis.chain_id.clone(),
+        })
+    }
+
+    pub fn get_policy(&self) -> &MonetaryPolicy {
+        &self.policy
+    }
+
+    /// Network the transactions of the chain are signed for.
+    pub fn get_chain_id(&self) -> &str {
+        &self.chain_id
+    }
+
+    /// Identifies the network the chain belongs to.
+    pub fn get_genesis_hash(&self) -> &Blockhash {
+        &self.genesis.block.blockhash
     }
 }
 
 impl Default for Blockchain {
     fn default() -> Self {
-        let genesis: Arc<Link> = Default::default();
-        Self {
-            latest_links: vec![genesis.clone()],
-            heighest_link: genesis.clone(),
-            genesis,
+        Self::new(&Genesis::default()).expect("the default genesis allocates nothing")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ed25519::{publickey::PublicKey, signature::Signature},
+        state::{amount::Amount, transaction::TransactionMessage},
+        utils::fixtures::*,
+    };
+
+    fn genesis_block() -> Block {
+        genesis().get_block()
+    }
+
+    fn blockchain() -> Blockchain {
+        Blockchain::new(&genesis()).unwrap()
+    }
+
+    fn mine_at(parent: &Block, timestamp: u64) -> Block {
+        (0..)
+            .map(|nonce| {
+                Block::create_block(
+                    parent.index + 1,
+                    &parent.blockhash,
+                    with_coinbase(parent.index + 1, vec![]),
+                    nonce,
+                    parent.difficulty,
+                    timestamp,
+                )
+            })
+            .find(|block| block.blockhash.is_valid(block.difficulty))
+            .unwrap()
+    }
+
+    mod fork_choice {
+        use super::*;
+
+        #[test]
+        fn follows_first_seen_on_tie() {
+            let mut blockchain = blockchain();
+            let genesis = blockchain.get_latest().block.clone();
+            let a1 = mine(&genesis, vec![], 0);
+            let b1 = mine(&genesis, vec![], a1.nonce + 1);
+
+            let update = blockchain.try_insert(&a1).unwrap();
+            assert_eq!(update.connected, vec![a1.clone()]);
+
+            let update = blockchain.try_insert(&b1).unwrap();
+            assert_eq!(update, ChainUpdate::default());
+            assert_eq!(blockchain.get_latest().block, a1);
+        }
+
+        #[test]
+        fn reorganizes_to_heaviest_chain() {
+            let mut blockchain = blockchain();
+            let genesis = blockchain.get_latest().block.clone();
+            let orphaned_tx = mint(&authority(), 1, 0, 10);
+            let a1 = mine(&genesis, vec![orphaned_tx.clone()], 0);
+            let b1 = mine(&genesis, vec![], 0);
+            let b2 = mine(&b1, vec![], 0);
+
+            blockchain.try_insert(&a1).unwrap();
+            blockchain.try_insert(&b1).unwrap();
+            let update = blockchain.try_insert(&b2).unwrap();
+
+            assert!(update.is_reorg());
+            assert_eq!(update.connected, vec![b1, b2.clone()]);
+            assert_eq!(update.disconnected, vec![a1]);
+            assert_eq!(update.orphaned_transactions(), vec![orphaned_tx]);
+            assert_eq!(blockchain.get_latest().block, b2);
+        }
+
+        #[test]
+        fn keeps_transactions_included_by_both_branches() {
+            let tx = mint(&keypair(), 1, 0, 10);
+            let update = ChainUpdate {
+                connected: vec![child(&genesis_block(), vec![tx.clone()], 1)],
+                disconnected: vec![child(&genesis_block(), vec![tx], 2)],
+            };
+            assert!(update.orphaned_transactions().is_empty());
+        }
+
+        #[test]
+        fn unknown_parent() {
+            let mut blockchain = blockchain();
+            let unknown = Block {
+                blockhash: Blockhash(vec![1; 32]),
+                ..genesis_block()
+            };
+            let block = mine(&unknown, vec![], 0);
+            assert_eq!(
+                blockchain.try_insert(&block),
+                Err(GulfStreamError::DidNotFindPreviousBlock)
+            );
+        }
+    }
+
+    mod lookup {
+        use super::*;
+
+        #[test]
+        fn gets_canonical_range() {
+            let mut blockchain = blockchain();
+            let genesis = genesis_block();
+            let a1 = mine(&genesis, vec![], 0);
+            let a2 = mine(&a1, vec![], 0);
+            let a3 = mine(&a2, vec![], 0);
+            let b1 = mine(&genesis, vec![], a1.nonce + 1);
+            for block in [&a1, &b1, &a2, &a3] {
+                blockchain.try_insert(block).unwrap();
+            }
+
+            assert_eq!(blockchain.get_blocks(1, 2), vec![a1.clone(), a2.clone()]);
+            assert_eq!(blockchain.get_blocks(2, 10), vec![a2, a3]);
+            assert_eq!(blockchain.get_blocks(0, 1), vec![genesis]);
+            assert!(blockchain.get_blocks(4, 10).is_empty());
+            assert!(blockchain.get_blocks(1, 0).is_empty());
+        }
+
+        #[test]
+        fn gets_fork_block_by_hash() {
+            let mut blockchain = blockchain();
+            let genesis = genesis_block();
+            let a1 = mine(&genesis, vec![], 0);
+            let b1 = mine(&genesis, vec![], a1.nonce + 1);
+            let mut parent = a1.clone();
+            blockchain.try_insert(&a1).unwrap();
+            blockchain.try_insert(&b1).unwrap();
+            for _ in 0..Blockchain::LASTEST_LINK_LENGTH {
+                parent = mine(&parent, vec![], 0);
+                blockchain.try_insert(&parent).unwrap();
+            }
+
+            assert_eq!(blockchain.get_block(&b1.blockhash), Some(b1.clone()));
+            assert_eq!(blockchain.get_block(&parent.blockhash), Some(parent));
+
+            let b2 = mine(&b1, vec![], 0);
+            blockchain.try_insert(&b2).unwrap();
+            assert_eq!(blockchain.get_block(&b2.blockhash), Some(b2));
+            assert_eq!(blockchain.get_block(&Blockhash(vec![1; 32])), None);
+        }
+    }
+
+    mod orphans {
+        use super::*;
+
+        #[test]
+        fn rejects_junk_orphans() {
+            let mut blockchain = blockchain();
+            let b1 = mine(&genesis_block(), vec![], 0);
+            let b2 = mine(&b1, vec![], 0);
+
+            let forged = Block {
+                blockhash: Blockhash(vec![0; 32]),
+                ..b2.clone()
+            };
+            assert_eq!(
+                blockchain.try_insert(&forged),
+                Err(GulfStreamError::InvalidBlockhash)
+            );
+            let unmined = (0..)
+                .map(|nonce| child(&b1, vec![], nonce))
+                .find(|block| !block.blockhash.is_valid(block.difficulty))
+                .unwrap();
+            assert_eq!(
+                blockchain.try_insert(&unmined),
+                Err(GulfStreamError::InsufficientProofOfWork)
+            );
+            let far = Block {
+                index: OrphanPool::MAX_DISTANCE + 2,
+                ..b2.clone()
+            };
+            let far = mine(&far, vec![], 0);
+            assert_eq!(
+                blockchain.try_insert(&far),
+                Err(GulfStreamError::WrongIndex)
+            );
+            assert_eq!(blockchain.get_orphan_count(), 0);
+        }
+
+        #[test]
+        fn connects_orphans_once_parent_arrives() {
+            let mut blockchain = blockchain();
+            let b1 = mine(&genesis_block(), vec![], 0);
+            let b2 = mine(&b1, vec![], 0);
+            let b3 = mine(&b2, vec![], 0);
+
+            for orphan in [&b3, &b2] {
+                assert_eq!(
+                    blockchain.try_insert(orphan),
+                    Err(GulfStreamError::DidNotFindPreviousBlock)
+                );
+            }
+            assert_eq!(blockchain.get_orphan_count(), 2);
+
+            let update = blockchain.try_insert(&b1).unwrap();
+            assert_eq!(update.connected, vec![b1.clone()]);
+            let (connected_orphans, update) = blockchain.connect_orphans(&b1.blockhash);
+            assert_eq!(connected_orphans, vec![b2.clone(), b3.clone()]);
+            assert_eq!(update.connected, vec![b2, b3.clone()]);
+            assert_eq!(blockchain.get_orphan_count(), 0);
+            assert_eq!(blockchain.get_latest().block, b3);
+        }
+
+        #[test]
+        fn rejects_known_block() {
+            let mut blockchain = blockchain();
+            let b1 = mine(&genesis_block(), vec![], 0);
+            blockchain.try_insert(&b1).unwrap();
+            assert_eq!(
+                blockchain.try_insert(&b1),
+                Err(GulfStreamError::BlockAlreadyKnown)
+            );
+        }
+
+        #[test]
+        fn merges_successive_updates() {
+            let genesis = genesis_block();
+            let a1 = mine(&genesis, vec![], 0);
+            let b1 = mine(&genesis, vec![], a1.nonce + 1);
+            let b2 = mine(&b1, vec![], 0);
+            let mut update = ChainUpdate {
+                connected: vec![a1.clone()],
+                disconnected: vec![],
+            };
+            update.merge(ChainUpdate {
+                connected: vec![b1.clone(), b2.clone()],
+                disconnected: vec![a1],
+            });
+            assert_eq!(update.connected, vec![b1, b2]);
+            assert!(update.disconnected.is_empty());
+        }
+    }
+
+    mod genesis {
+        use super::*;
+
+        #[test]
+        fn credits_genesis_allocations() {
+            let (alice, bob) = (keypair(), keypair());
+            let genesis = Genesis {
+                allocations: vec![(PublicKey(alice.public), 100)],
+                ..genesis()
+            };
+            let mut blockchain = Blockchain::new(&genesis).unwrap();
+            let block1 = mine(
+                &genesis.get_block(),
+                vec![transfer(&alice, 1, 0, &bob, 40)],
+                0,
+            );
+            blockchain.try_insert(&block1).unwrap();
+
+            let head = blockchain.get_latest();
+            assert_eq!(
+                head.get_balance(&PublicKey(alice.public)),
+                crate::state::transaction::BalanceDelta::Pos(Amount(60))
+            );
+            assert_eq!(head.get_nonce(&PublicKey(alice.public)), 1);
+        }
+    }
+
+    mod restore {
+        use super::*;
+
+        #[test]
+        fn rebuilds_accounts_from_unordered_blocks() {
+            let (alice, bob) = (authority(), keypair());
+            let block1 = mine(&genesis_block(), vec![mint(&alice, 1, 0, 10)], 0);
+            let block2 = mine(&block1, vec![transfer(&alice, 2, 1, &bob, 4)], 0);
+
+            let blockchain = Blockchain::restore(
+                &genesis(),
+                vec![block2.clone(), genesis_block(), block1],
+                None,
+            )
+            .unwrap();
+            let head = blockchain.get_latest();
+            assert_eq!(head.block, block2);
+            assert_eq!(head.get_nonce(&PublicKey(alice.public)), 2);
+            assert_eq!(
+                head.get_balance(&PublicKey(bob.public)),
+                crate::state::transaction::BalanceDelta::Pos(Amount(4))
+            );
+        }
+
+        #[test]
+        fn keeps_stored_head_on_tie() {
+            let root = genesis_block();
+            let a1 = mine(&root, vec![], 0);
+            let b1 = mine(&root, vec![], a1.nonce + 1);
+
+            let blockchain = Blockchain::restore(
+                &genesis(),
+                vec![a1.clone(), b1.clone()],
+                Some(&b1.blockhash),
+            )
+            .unwrap();
+            assert_eq!(blockchain.get_latest().block, b1);
+
+            let blockchain = Blockchain::restore(&genesis(), vec![a1.clone(), b1], None).unwrap();
+            assert_eq!(blockchain.get_latest().block, a1);
+        }
+    }
+
+    mod validation {
+        use super::*;
+
+        #[test]
+        fn accepts_funded_transfer() {
+            let mut blockchain = blockchain();
+            let (alice, bob) = (authority(), keypair());
+            let block1 = mine(&genesis_block(), vec![mint(&alice, 1, 0, 10)], 0);
+            let block2 = mine(&block1, vec![transfer(&alice, 2, 1, &bob, 10)], 0);
+
+            blockchain.try_insert(&block1).unwrap();
+            blockchain.try_insert(&block2).unwrap();
+        }
+
+        #[test]
+        fn rejects_forged_blockhash() {
+            let mut block = mine(&genesis_block(), vec![], 0);
+            block.blockhash = Blockhash(vec![0; 32]);
+            assert_eq!(
+                blockchain().try_insert(&block),
+                Err(GulfStreamError::InvalidBlockhash)
+            );
+        }
+
+        #[test]
+        fn rejects_missing_proof_of_work() {
+            let block = (0..)
+                .map(|nonce| child(&genesis_block(), vec![], nonce))
+                .find(|block| !block.blockhash.is_valid(block.difficulty))
+                .unwrap();
+            assert_eq!(
+                blockchain().try_insert(&block),
+                Err(GulfStreamError::InsufficientProofOfWork)
+            );
+        }
+
+        #[test]
+        fn rejects_wrong_difficulty() {
+            let block = mine(&genesis_block(), vec![], 0);
+            let easier = (0..)
+                .map(|nonce| {
+                    Block::create_block(
+                        block.index,
+                        &block.previous_blockhash,
+                        vec![],
+                        nonce,
+                        block.difficulty - 1,
+                        block.timestamp,
+                    )
+                })
+                .find(|block| block.blockhash.is_valid(block.difficulty))
+                .unwrap();
+            assert_eq!(
+                blockchain().try_insert(&easier),
+                Err(GulfStreamError::WrongDifficulty)
+            );
+        }
+
+        #[test]
+        fn rejects_timestamp_below_median_time_past() {
+            let mut blockchain = blockchain();
+            let mut parent = genesis_block();
+            for _ in 0..Blockchain::MEDIAN_TIME_SPAN {
+                parent = mine(&parent, vec![], 0);
+                blockchain.try_insert(&parent).unwrap();
+            }
+            let median = Blockchain::median_time_past(&blockchain.get_latest());
+            let block = mine_at(&parent, median);
+            assert_eq!(
+                blockchain.try_insert(&block),
+                Err(GulfStreamError::BlockTooOld)
+            );
+            blockchain
+                .try_insert(&mine_at(&parent, median + 1))
+                .unwrap();
+        }
+
+        #[test]
+        fn rejects_timestamp_in_future() {
+            let timestamp = unix_timestamp() + Blockchain::MAX_FUTURE_BLOCK_TIME + 60;
+            let block = mine_at(&genesis_block(), timestamp);
+            assert_eq!(
+                blockchain().try_insert(&block),
+                Err(GulfStreamError::BlockTooFarInFuture)
+            );
+        }
+
+        #[test]
+        fn rejects_forged_signature() {
+            let mut tx = mint(&keypair(), 1, 0, 10);
+            tx.signature = Default::default();
+            let block = mine(&genesis_block(), vec![tx], 0);
+            assert_eq!(
+                blockchain().try_insert(&block),
+                Err(GulfStreamError::InvalidTxSignature)
+            );
+        }
+
+        #[test]
+        fn rejects_self_transfer() {
+            let alice = keypair();
+            let block = mine(&genesis_block(), vec![transfer(&alice, 1, 0, &alice, 0)], 0);
+            assert_eq!(
+                blockchain().try_insert(&block),
+                Err(GulfStreamError::InvalidTxMessage)
+            );
+        }
+
+        #[test]
+        fn accepts_transaction_anywhere_in_window() {
+            let mut blockchain = blockchain();
+            let block1 = mine(&genesis_block(), vec![], 0);
+            blockchain.try_insert(&block1).unwrap();
+            let block2 = mine(&block1, vec![mint(&authority(), 1, 0, 10)], 0);
+            blockchain.try_insert(&block2).unwrap();
+        }
+
+        #[test]
+        fn rejects_transaction_outside_window() {
+            let early = mine(&genesis_block(), vec![mint(&keypair(), 2, 0, 10)], 0);
+            assert_eq!(
+                blockchain().try_insert(&early),
+                Err(GulfStreamError::TxOutsideValidityWindow)
+            );
+
+            let msg = TransactionMessage::Mint { amount: Amount(10) };
+            let expired_tx =
+                Signature::sign_payload(&keypair(), Genesis::DEFAULT_CHAIN_ID, 0, 0, 0, 0, msg);
+            let expired = mine(&genesis_block(), vec![expired_tx], 0);
+            assert_eq!(
+                blockchain().try_insert(&expired),
+                Err(GulfStreamError::TxOutsideValidityWindow)
+            );
+        }
+
+        #[test]
+        fn rejects_too_long_window() {
+            let msg = TransactionMessage::Mint { amount: Amount(10) };
+            let valid_until = Transaction::MAX_VALIDITY_WINDOW + 2;
+            let tx = Signature::sign_payload(
+                &keypair(),
+                Genesis::DEFAULT_CHAIN_ID,
+                1,
+                valid_until,
+                0,
+                0,
+                msg,
+            );
+            let block = mine(&genesis_block(), vec![tx], 0);
+            assert_eq!(
+                blockchain().try_insert(&block),
+                Err(GulfStreamError::InvalidValidityWindow)
+            );
+        }
+
+        #[test]
+        fn rejects_replayed_nonce() {
+            let mut blockchain = blockchain();
+            let alice = authority();
+            let block1 = mine(&genesis_block(), vec![mint(&alice, 1, 0, 10)], 0);
+            blockchain.try_insert(&block1).unwrap();
+
+            let replayed = mine(&block1, vec![mint(&alice, 2, 0, 10)], 0);
+            assert_eq!(
+                blockchain.try_insert(&replayed),
+                Err(GulfStreamError::WrongTxNonce)
+            );
+            let skipped = mine(&block1, vec![mint(&alice, 2, 2, 10)], 0);
+            assert_eq!(
+                blockchain.try_insert(&skipped),
+                Err(GulfStreamError::WrongTxNonce)
+            );
+            blockchain
+                .try_insert(&mine(&block1, vec![mint(&alice, 2, 1, 10)], 0))
+                .unwrap();
+        }
+
+        #[test]
+        fn rejects_overspending() {
+            let (alice, bob) = (authority(), keypair());
+            let block = mine(
+                &genesis_block(),
+                vec![mint(&alice, 1, 0, 10), transfer(&alice, 1, 1, &bob, 11)],
+                0,
+            );
+            assert_eq!(
+                blockchain().try_insert(&block),
+                Err(GulfStreamError::Overspending)
+            );
+        }
+
+        #[test]
+        fn rejects_balance_overflow() {
+            let alice = authority();
+            let block = mine(
+                &genesis_block(),
+                vec![mint(&alice, 1, 0, u64::MAX), mint(&alice, 1, 1, 1)],
+                0,
+            );
+            assert_eq!(
+                blockchain().try_insert(&block),
+                Err(GulfStreamError::AmountOverflow)
+            );
+        }
+    }
+
+    mod policy {
+        use super::*;
+
+        #[test]
+        fn rejects_unauthorized_mint() {
+            let block = mine(&genesis_block(), vec![mint(&keypair(), 1, 0, 10)], 0);
+            assert_eq!(
+                blockchain().try_insert(&block),
+                Err(GulfStreamError::UnauthorizedMint)
+            );
+        }
+
+        #[test]
+        fn pays_coinbase_without_using_nonce() {
+            let mut blockchain = blockchain();
+            let (miner, bob) = (keypair(), keypair());
+            let reward = blockchain.get_policy().get_block_reward(1);
+            let coinbase = blockchain
+                .get_policy()
+                .create_coinbase(&miner, Genesis::DEFAULT_CHAIN_ID, 1, Amount::ZERO)
+                .unwrap();
+            let block1 = mine(
+                &genesis_block(),
+                vec![coinbase, transfer(&miner, 1, 0, &bob, reward)],
+                0,
+            );
+            blockchain.try_insert(&block1).unwrap();
+
+            let head = blockchain.get_latest();
+            assert_eq!(head.get_nonce(&PublicKey(miner.public)), 1);
+            assert_eq!(
+                head.get_balance(&PublicKey(bob.public)),
+                crate::state::transaction::BalanceDelta::Pos(Amount(reward))
+            );
+        }
+
+        #[test]
+        fn pays_fees_to_producer() {
+            let mut blockchain = blockchain();
+            let (miner, alice) = (keypair(), authority());
+            let reward = blockchain.get_policy().get_block_reward(1);
+            let paid = |gas| {
+                let msg = TransactionMessage::Mint { amount: Amount(10) };
+                Signature::sign_payload(&alice, Genesis::DEFAULT_CHAIN_ID, 1, 1, gas, 0, msg)
+            };
+            let over_claim = blockchain
+                .get_policy()
+                .create_coinbase(&miner, Genesis::DEFAULT_CHAIN_ID, 1, Amount(4))
+                .unwrap();
+            let block = mine(&genesis_block(), vec![over_claim, paid(3)], 0);
+            assert_eq!(
+                blockchain.try_insert(&block),
+                Err(GulfStreamError::InvalidCoinbase)
+            );
+
+            let coinbase = blockchain
+                .get_policy()
+                .create_coinbase(&miner, Genesis::DEFAULT_CHAIN_ID, 1, Amount(3))
+                .unwrap();
+            let block = mine(&genesis_block(), vec![coinbase, paid(3)], 0);
+            blockchain.try_insert(&block).unwrap();
+
+            let head = blockchain.get_latest();
+            assert_eq!(
+                head.get_balance(&PublicKey(miner.public)),
+                crate::state::transaction::BalanceDelta::Pos(Amount(reward + 3))
+            );
+            assert_eq!(
+                head.get_balance(&PublicKey(alice.public)),
+                crate::state::transaction::BalanceDelta::Pos(Amount(7))
+            );
+        }
+
+        #[test]
+        fn rejects_coinbase_after_transactions() {
+            let miner = keypair();
+            let coinbase = policy()
+                .create_coinbase(&miner, Genesis::DEFAULT_CHAIN_ID, 1, Amount::ZERO)
+                .unwrap();
+            let block = mine(
+                &genesis_block(),
+                vec![mint(&authority(), 1, 0, 10), coinbase],
+                0,
+            );
+            assert_eq!(
+                blockchain().try_insert(&block),
+                Err(GulfStreamError::InvalidCoinbase)
+            );
         }
     }
 }
@@ -1,7 +1,7 @@
 use super::transaction::Transaction;
 use sha2::{Digest, Sha256};
 
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct Blockhash(pub Vec<u8>);
 
 impl std::fmt::Display for Blockhash {
@@ -34,14 +34,18 @@ impl Blockhash {
         previous_blockhash: &Blockhash,
         transactions: &Vec<Transaction>,
         nonce: u64,
+        difficulty: u32,
+        timestamp: u64,
     ) -> Blockhash {
-        let mut hasher = Sha256::new();
-        hasher.update(nonce.to_be_bytes());
-        hasher.update(index.to_be_bytes());
-        hasher.update(previous_blockhash);
         let raw_txs = Transaction::get_raw_txs(transactions);
-        hasher.update(raw_txs);
-        hasher.finalize().to_vec().into()
+        Self::from_raw_data(
+            index,
+            previous_blockhash,
+            &raw_txs,
+            nonce,
+            difficulty,
+            timestamp,
+        )
     }
 
     pub fn from_raw_data(
@@ -49,17 +53,46 @@ impl Blockhash {
         previous_blockhash: &Blockhash,
         transactions: &Vec<u8>,
         nonce: u64,
+        difficulty: u32,
+        timestamp: u64,
     ) -> Blockhash {
         let mut hasher = Sha256::new();
         hasher.update(nonce.to_be_bytes());
         hasher.update(index.to_be_bytes());
+        hasher.update(difficulty.to_be_bytes());
+        hasher.update(timestamp.to_be_bytes());
         hasher.update(previous_blockhash);
         hasher.update(transactions);
         hasher.finalize().to_vec().into()
     }
 
-    pub fn is_valid(&self, difficulty: usize) -> bool {
-        let slice = &self.0[0..difficulty];
-        slice.into_iter().any(|value| value.eq(&0))
+    pub fn leading_zero_bits(&self) -> u32 {
+        let mut bits = 0;
+        for byte in self.0.iter() {
+            bits += byte.leading_zeros();
+            if *byte != 0 {
+                break;
+            }
+        }
+        bits
+    }
+
+    /// Proof of work target : the hash must start with at least `difficulty` zero bits.
+    pub fn is_valid(&self, difficulty: u32) -> bool {
+        self.leading_zero_bits() >= difficulty
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn leading_zero_bits() {
+        assert_eq!(Blockhash(vec![0xff, 0]).leading_zero_bits(), 0);
+        assert_eq!(Blockhash(vec![0, 0x10, 0]).leading_zero_bits(), 11);
+        assert_eq!(Blockhash(vec![0, 0]).leading_zero_bits(), 16);
+        assert!(Blockhash(vec![0, 0x10]).is_valid(11));
+        assert!(!Blockhash(vec![0, 0x10]).is_valid(12));
     }
 }
@@ -0,0 +1,70 @@
+use super::link::Link;
+use std::sync::Arc;
+
+/// Difficulty of the genesis block, in leading zero bits.
+pub const INITIAL_DIFFICULTY: u32 = 8;
+pub const MIN_DIFFICULTY: u32 = 1;
+pub const MAX_DIFFICULTY: u32 = 128;
+/// Number of blocks between two difficulty adjustments.
+pub const RETARGET_INTERVAL: u64 = 10;
+/// Block time the retargeting aims for, in seconds.
+pub const TARGET_BLOCK_TIME: u64 = 5;
+/// Bound on a single adjustment, in bits. One bit doubles or halves the expected work.
+pub const MAX_ADJUSTMENT: u32 = 2;
+
+/// Difficulty the child of `parent` must declare. It only changes on retarget heights, where
+/// it is adjusted by the time the previous window took compared to the target block time.
+pub fn next_difficulty(parent: &Arc<Link>) -> u32 {
+    let next_index = parent.block.index + 1;
+    if next_index % RETARGET_INTERVAL != 0 {
+        return parent.block.difficulty;
+    }
+    // The genesis timestamp is not a mining time, so it never opens a window.
+    let first_index = (next_index - RETARGET_INTERVAL).max(1);
+    match parent.clone().get_ancestor(first_index) {
+        Some(first) if first.block.index < parent.block.index => retarget(
+            parent.block.difficulty,
+            parent.block.timestamp.saturating_sub(first.block.timestamp),
+            (parent.block.index - first.block.index) * TARGET_BLOCK_TIME,
+        ),
+        _ => parent.block.difficulty,
+    }
+}
+
+/// Adds a bit each time the window was twice as fast as expected and removes one each time
+/// it was twice as slow, within `MAX_ADJUSTMENT` bits.
+pub fn retarget(difficulty: u32, elapsed: u64, expected: u64) -> u32 {
+    let mut difficulty = difficulty;
+    let mut elapsed = elapsed.max(1);
+    let mut adjustment = 0;
+    while elapsed * 2 <= expected && adjustment < MAX_ADJUSTMENT {
+        difficulty += 1;
+        elapsed *= 2;
+        adjustment += 1;
+    }
+    while elapsed >= expected * 2 && adjustment < MAX_ADJUSTMENT {
+        difficulty = difficulty.saturating_sub(1);
+        elapsed /= 2;
+        adjustment += 1;
+    }
+    difficulty.clamp(MIN_DIFFICULTY, MAX_DIFFICULTY)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn retarget_within_bounds() {
+        let expected = RETARGET_INTERVAL * TARGET_BLOCK_TIME;
+        assert_eq!(retarget(8, expected, expected), 8);
+        assert_eq!(retarget(8, expected / 2, expected), 9);
+        assert_eq!(retarget(8, 0, expected), 8 + MAX_ADJUSTMENT);
+        assert_eq!(retarget(8, expected * 2, expected), 7);
+        assert_eq!(retarget(8, expected * 100, expected), 8 - MAX_ADJUSTMENT);
+        assert_eq!(
+            retarget(MIN_DIFFICULTY, expected * 100, expected),
+            MIN_DIFFICULTY
+        );
+    }
+}
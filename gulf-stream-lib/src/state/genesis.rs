@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::{
+    amount::Amount,
+    block::Block,
+    blockhash::Blockhash,
+    difficulty::{INITIAL_DIFFICULTY, MAX_DIFFICULTY, MIN_DIFFICULTY},
+    policy::MonetaryPolicy,
+    transaction::{Transaction, TransactionMessage},
+};
+use crate::{
+    ed25519::{publickey::PublicKey, signature::Signature},
+    err::GulfStreamError,
+    utils::serde::BytesSerialize,
+};
+
+/// What a network starts from. Every field goes into the genesis block, so two nodes agree
+/// on the rules and the initial balances exactly when they agree on the genesis blockhash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Genesis {
+    pub chain_id: String,
+    /// Seconds since the unix epoch.
+    pub timestamp: u64,
+    pub initial_difficulty: u32,
+    /// Balances credited by the genesis block, without using a nonce.
+    pub allocations: Vec<(PublicKey, u64)>,
+    pub policy: MonetaryPolicy,
+}
+
+impl Default for Genesis {
+    fn default() -> Self {
+        Self {
+            chain_id: Self::DEFAULT_CHAIN_ID.into(),
+            timestamp: 0,
+            initial_difficulty: INITIAL_DIFFICULTY,
+            allocations: vec![],
+            policy: MonetaryPolicy::default(),
+        }
+    }
+}
+
+/// Layout of the genesis file, keys in base58.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GenesisFile {
+    chain_id: String,
+    #[serde(default)]
+    timestamp: u64,
+    #[serde(default = "default_difficulty")]
+    initial_difficulty: u32,
+    #[serde(default)]
+    allocations: BTreeMap<String, u64>,
+    #[serde(default)]
+    mint_authorities: Vec<String>,
+    #[serde(default = "default_reward")]
+    initial_reward: u64,
+    #[serde(default = "default_halving_interval")]
+    halving_interval: u64,
+}
+
+fn default_difficulty() -> u32 {
+    INITIAL_DIFFICULTY
+}
+
+fn default_reward() -> u64 {
+    MonetaryPolicy::DEFAULT_INITIAL_REWARD
+}
+
+fn default_halving_interval() -> u64 {
+    MonetaryPolicy::DEFAULT_HALVING_INTERVAL
+}
+
+impl Genesis {
+    pub const DEFAULT_CHAIN_ID: &str = "gulf-stream";
+
+    pub fn from_json(json: &str) -> Result<Self, GulfStreamError> {
+        let file: GenesisFile = serde_json::from_str(json)
+            .map_err(|err| GulfStreamError::InvalidGenesis(err.to_string()))?;
+        let parse_pk = |pk: &String| {
+            PublicKey::try_from_str(pk)
+                .map_err(|_| GulfStreamError::InvalidGenesis(format!("bad public key {pk}")))
+        };
+        if file.chain_id.is_empty() {
+            return Err(GulfStreamError::InvalidGenesis("empty chain id".into()));
+        }
+        if !(MIN_DIFFICULTY..=MAX_DIFFICULTY).contains(&file.initial_difficulty) {
+            return Err(GulfStreamError::InvalidGenesis(format!(
+                "initial difficulty out of {MIN_DIFFICULTY}..={MAX_DIFFICULTY}"
+            )));
+        }
+        file.allocations
+            .values()
+            .try_fold(Amount::ZERO, |supply, amount| {
+                supply.checked_add(Amount(*amount))
+            })
+            .map_err(|_| GulfStreamError::InvalidGenesis("allocations overflow".into()))?;
+        Ok(Self {
+            chain_id: file.chain_id,
+            timestamp: file.timestamp,
+            initial_difficulty: file.initial_difficulty,
+            allocations: file
+                .allocations
+                .iter()
+                .map(|(pk, amount)| Ok((parse_pk(pk)?, *amount)))
+                .collect::<Result<_, GulfStreamError>>()?,
+            policy: MonetaryPolicy {
+                initial_reward: file.initial_reward,
+                halving_interval: file.halving_interval,
+                mint_authorities: file
+                    .mint_authorities
+                    .iter()
+                    .map(parse_pk)
+                    .collect::<Result<_, _>>()?,
+            },
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, GulfStreamError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|err| GulfStreamError::InvalidGenesis(err.to_string()))?;
+        Self::from_json(&json)
+    }
+
+    /// Stands in for the parent of the genesis block, so its hash covers the chain id and
+    /// the monetary policy.
+    fn get_rules_hash(&self) -> Blockhash {
+        let mut hasher = Sha256::new();
+        hasher.update(self.chain_id.as_bytes());
+        hasher.update(self.policy.initial_reward.to_be_bytes());
+        hasher.update(self.policy.halving_interval.to_be_bytes());
+        self.policy
+            .mint_authorities
+            .iter()
+            .for_each(|pk| hasher.update(pk.serialize()));
+        hasher.finalize().to_vec().into()
+    }
+
+    /// Allocations are coinbases nobody signed. Their signature slot holds the recipient
+    /// instead, which keeps it unique.
+    fn get_allocation(pk: &PublicKey, amount: u64) -> Transaction {
+        let mut signature = [0u8; ed25519_dalek::SIGNATURE_LENGTH];
+        signature[..ed25519_dalek::PUBLIC_KEY_LENGTH].copy_from_slice(&pk.serialize());
+        Transaction {
+            valid_from: 0,
+            valid_until: 0,
+            gas: 0,
+            nonce: 0,
+            msg: TransactionMessage::Coinbase {
+                amount: Amount(amount),
+            },
+            payer: pk.clone(),
+            signature: Signature(ed25519_dalek::Signature::from_bytes(&signature).unwrap()),
+        }
+    }
+
+    pub fn get_block(&self) -> Block {
+        let transactions = self
+            .allocations
+            .iter()
+            .map(|(pk, amount)| Self::get_allocation(pk, *amount))
+            .collect();
+        Block::create_block(
+            0,
+            &self.get_rules_hash(),
+            transactions,
+            0,
+            self.initial_difficulty,
+            self.timestamp,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ALICE: &str = "6JphWw6252kiBMLYUsJzc2sAhR5TFwXjU61ezi6My164";
+
+    #[test]
+    fn reads_genesis_file() {
+        let json = format!(
+            r#"{{"chain_id": "testnet", "allocations": {{"{ALICE}": 1000}}, "mint_authorities": ["{ALICE}"]}}"#
+        );
+        let genesis = Genesis::from_json(&json).unwrap();
+        let alice = PublicKey::try_from_str(ALICE).unwrap();
+
+        assert_eq!(genesis.chain_id, "testnet");
+        assert_eq!(genesis.initial_difficulty, INITIAL_DIFFICULTY);
+        assert_eq!(genesis.allocations, vec![(alice.clone(), 1000)]);
+        assert!(genesis.policy.can_mint(&alice));
+        assert_eq!(
+            genesis.get_block(),
+            Genesis::from_json(&json).unwrap().get_block()
+        );
+        assert!(Genesis::from_json(r#"{"chain_id": "testnet", "reward": 1}"#).is_err());
+        let bob = "8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR";
+        let overflowing = format!(
+            r#"{{"chain_id": "testnet", "allocations": {{"{ALICE}": {}, "{bob}": 1}}}}"#,
+            u64::MAX
+        );
+        assert_eq!(
+            Genesis::from_json(&overflowing),
+            Err(GulfStreamError::InvalidGenesis(
+                "allocations overflow".into()
+            ))
+        );
+        for difficulty in [0, MAX_DIFFICULTY + 1] {
+            let json = format!(r#"{{"chain_id": "testnet", "initial_difficulty": {difficulty}}}"#);
+            assert!(matches!(
+                Genesis::from_json(&json),
+                Err(GulfStreamError::InvalidGenesis(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn identifies_network() {
+        let genesis = Genesis::default();
+        let other = Genesis {
+            chain_id: "other".into(),
+            ..Default::default()
+        };
+        let richer = Genesis {
+            allocations: vec![(PublicKey::random(), 10)],
+            ..Default::default()
+        };
+
+        assert_ne!(genesis.get_block().blockhash, other.get_block().blockhash);
+        assert_ne!(genesis.get_block().blockhash, richer.get_block().blockhash);
+    }
+}
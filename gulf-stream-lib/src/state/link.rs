@@ -1,13 +1,13 @@
 use super::{
+    account::{Account, AccountState},
     block::Block,
     blockhash::Blockhash,
-    transaction::{BalanceDelta, Transaction},
+    transaction::BalanceDelta,
 };
 use crate::{ed25519::publickey::PublicKey, err::*};
 use std::{
     collections::HashMap,
     fmt::Display,
-    ops::Add,
     sync::{Arc, Mutex},
 };
 
@@ -16,6 +16,9 @@ pub struct Link {
     pub block_parent: Option<Arc<Link>>,
     pub block: Block,
     pub next_blocks: Mutex<Vec<Arc<Link>>>,
+    /// Cumulative work of the chain ending at this block.
+    pub chain_work: u128,
+    pub accounts: AccountState,
 }
 
 impl Link {
@@ -57,33 +60,82 @@ impl Link {
         };
     }
 
-    pub fn get_balance(&self, pk: &PublicKey) -> BalanceDelta {
-        let current_delta = self
-            .block
-            .get_balance_deltas()
-            .get(&pk)
-            .unwrap_or(&BalanceDelta::default())
-            .clone();
-        return if let Some(block_parent) = &self.block_parent {
-            let last_delta = block_parent.clone().get_balance(pk);
-            current_delta.add(last_delta)
-        } else {
-            current_delta
+    /// State of `pk` once this block is applied. Walks back the account layers until one
+    /// knows `pk` or holds a checkpoint.
+    pub fn get_account(&self, pk: &PublicKey) -> Account {
+        let mut current_link = self;
+        loop {
+            if let Some(account) = current_link.accounts.get(pk) {
+                return account;
+            }
+            match &current_link.block_parent {
+                Some(block_parent) => current_link = block_parent,
+                None => return Account::default(),
+            }
+        }
+    }
+
+    /// Whole account state once this block is applied : the closest checkpoint with the
+    /// layers above it.
+    pub fn get_accounts(&self) -> HashMap<PublicKey, Account> {
+        let mut diffs = vec![];
+        let mut current_link = self;
+        let mut accounts = loop {
+            if let Some(checkpoint) = current_link.accounts.get_checkpoint() {
+                break checkpoint.as_ref().clone();
+            }
+            diffs.push(current_link.accounts.get_diff());
+            match &current_link.block_parent {
+                Some(block_parent) => current_link = block_parent,
+                None => break HashMap::new(),
+            }
         };
+        diffs.into_iter().rev().for_each(|diff| {
+            accounts.extend(diff.iter().map(|(pk, account)| (pk.clone(), *account)))
+        });
+        accounts
+    }
+
+    pub fn get_balance(&self, pk: &PublicKey) -> BalanceDelta {
+        self.get_account(pk).balance
+    }
+
+    /// Number of transactions `pk` paid for on this chain, which is the nonce its next
+    /// transaction must carry.
+    pub fn get_nonce(&self, pk: &PublicKey) -> u64 {
+        self.get_account(pk).nonce
+    }
+
+    pub fn get_nonces(&self, pks: &Vec<PublicKey>) -> HashMap<PublicKey, u64> {
+        let mut nonces = HashMap::new();
+        for pk in pks {
+            nonces.insert(pk.clone(), self.get_nonce(&pk));
+        }
+        return nonces;
     }
 
     pub fn get_balances(&self, pks: &Vec<PublicKey>) -> HashMap<PublicKey, BalanceDelta> {
         let mut balances = HashMap::new();
-        // todo: optimize
         for pk in pks {
             balances.insert(pk.clone(), self.get_balance(&pk));
         }
         return balances;
     }
 
+    /// First link of a chain, holding the allocations of `genesis`.
+    pub fn new_root(genesis: Block) -> Result<Self, GulfStreamError> {
+        Ok(Self {
+            accounts: AccountState::new(None, &genesis)?,
+            block: genesis,
+            ..Default::default()
+        })
+    }
+
     fn unsafe_insert(self: Arc<Link>, block: Block) -> Result<Arc<Link>, GulfStreamError> {
         let new_link = Arc::new(Self {
             block_parent: self.clone().into(),
+            chain_work: self.chain_work + block.work(),
+            accounts: AccountState::new(Some(&self), &block)?,
             block,
             next_blocks: vec![].into(),
         });
@@ -91,18 +143,39 @@ impl Link {
         return Ok(new_link);
     }
 
-    pub fn get_transaction_history(self: Arc<Link>) -> Vec<Transaction> {
-        let mut history = vec![];
-        let mut current_link = self;
-        loop {
-            history.extend(current_link.block.transactions.clone());
-            if let Some(block_parent) = &current_link.block_parent {
-                current_link = block_parent.clone();
-            } else {
+    pub fn get_parent(&self) -> Option<Arc<Link>> {
+        self.block_parent.clone()
+    }
+
+    /// Median timestamp of this block and its ancestors, over at most `span` blocks.
+    pub fn median_time_past(self: Arc<Link>, span: usize) -> u64 {
+        let mut timestamps = vec![];
+        let mut current_link = Some(self);
+        while let Some(link) = current_link {
+            if timestamps.len() >= span {
                 break;
             }
+            timestamps.push(link.block.timestamp);
+            current_link = link.get_parent();
+        }
+        timestamps.sort_unstable();
+        timestamps
+            .get(timestamps.len() / 2)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Walks back the parents until the link at `index`.
+    pub fn get_ancestor(self: Arc<Link>, index: u64) -> Option<Arc<Link>> {
+        let mut current_link = self;
+        while current_link.block.index > index {
+            current_link = current_link.get_parent()?;
+        }
+        if current_link.block.index == index {
+            Some(current_link)
+        } else {
+            None
         }
-        return history;
     }
 }
 
@@ -125,6 +198,7 @@ impl Display for Link {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::state::amount::Amount;
 
     mod get_balance {
         use crate::state::transaction::{Transaction, TransactionMessage};
@@ -142,38 +216,48 @@ mod test {
                 &link.block.blockhash,
                 vec![
                     Transaction {
-                        blockheight: 1,
-                        msg: TransactionMessage::Mint { amount: 12 },
+                        valid_from: 1,
+                        valid_until: 1,
+                        msg: TransactionMessage::Mint { amount: Amount(12) },
                         payer: pk1.to_owned(),
                         signature: Default::default(),
                         gas: 0,
+                        nonce: 0,
                     },
                     Transaction {
-                        blockheight: 1,
+                        valid_from: 1,
+                        valid_until: 1,
 
-                        msg: TransactionMessage::Mint { amount: 57 },
+                        msg: TransactionMessage::Mint { amount: Amount(57) },
                         payer: pk2.to_owned(),
                         signature: Default::default(),
                         gas: 0,
+                        nonce: 0,
                     },
                 ],
                 0,
+                0,
+                0,
             );
 
             let block2 = Block::create_block(
                 2,
                 &block1.blockhash,
                 vec![Transaction {
-                    blockheight: 2,
+                    valid_from: 2,
+                    valid_until: 2,
                     msg: TransactionMessage::Transfer {
                         to: pk2.to_owned(),
-                        amount: 5,
+                        amount: Amount(5),
                     },
                     payer: pk1.to_owned(),
                     signature: Default::default(),
                     gas: 0,
+                    nonce: 1,
                 }],
                 0,
+                0,
+                0,
             );
 
             link.clone().try_insert(&block1).unwrap();
@@ -194,8 +278,8 @@ mod test {
                 .unwrap()
                 .get_balance(&pk2);
 
-            assert_eq!(balance1, BalanceDelta::Pos(7));
-            assert_eq!(balance2, BalanceDelta::Pos(62));
+            assert_eq!(balance1, BalanceDelta::Pos(Amount(7)));
+            assert_eq!(balance2, BalanceDelta::Pos(Amount(62)));
         }
     }
 }
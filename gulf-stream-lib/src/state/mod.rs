@@ -1,5 +1,11 @@
+pub mod account;
+pub mod amount;
 pub mod block;
 pub mod blockchain;
 pub mod blockhash;
+pub mod difficulty;
+pub mod genesis;
 pub mod link;
+pub mod orphan;
+pub mod policy;
 pub mod transaction;
@@ -0,0 +1,131 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::{block::Block, blockhash::Blockhash};
+
+/// Blocks whose parent is unknown, indexed by the parent they wait for. Once full, the
+/// oldest orphan makes room for the new one.
+pub struct OrphanPool {
+    max_size: usize,
+    by_parent: HashMap<Blockhash, Vec<Block>>,
+    /// Orphans by arrival, oldest first.
+    arrival: VecDeque<(Blockhash, Blockhash)>,
+}
+
+impl OrphanPool {
+    pub const DEFAULT_MAX_SIZE: usize = 100;
+    /// How far above the head an orphan may claim to be.
+    pub const MAX_DISTANCE: u64 = 100;
+
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            by_parent: HashMap::new(),
+            arrival: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.arrival.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arrival.is_empty()
+    }
+
+    pub fn contains(&self, blockhash: &Blockhash) -> bool {
+        self.arrival
+            .iter()
+            .any(|(orphan_blockhash, _)| orphan_blockhash.eq(blockhash))
+    }
+
+    /// Returns false if the block was already in the pool.
+    pub fn insert(&mut self, block: Block) -> bool {
+        if self.max_size == 0 || self.contains(&block.blockhash) {
+            return false;
+        }
+        if self.len() >= self.max_size {
+            self.evict_oldest();
+        }
+        self.arrival
+            .push_back((block.blockhash.clone(), block.previous_blockhash.clone()));
+        self.by_parent
+            .entry(block.previous_blockhash.clone())
+            .or_default()
+            .push(block);
+        true
+    }
+
+    /// Removes and returns the orphans waiting for `parent`.
+    pub fn take_children(&mut self, parent: &Blockhash) -> Vec<Block> {
+        let children = self.by_parent.remove(parent).unwrap_or_default();
+        self.arrival
+            .retain(|(_, previous_blockhash)| previous_blockhash.ne(parent));
+        children
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some((blockhash, previous_blockhash)) = self.arrival.pop_front() {
+            if let Some(siblings) = self.by_parent.get_mut(&previous_blockhash) {
+                siblings.retain(|block| block.blockhash.ne(&blockhash));
+                if siblings.is_empty() {
+                    self.by_parent.remove(&previous_blockhash);
+                }
+            }
+        }
+    }
+}
+
+impl Default for OrphanPool {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn child(parent: &Block, nonce: u64) -> Block {
+        Block::create_block(
+            parent.index + 1,
+            &parent.blockhash,
+            vec![],
+            nonce,
+            parent.difficulty,
+            parent.timestamp,
+        )
+    }
+
+    #[test]
+    fn takes_children_of_parent() {
+        let mut pool = OrphanPool::default();
+        let parent = child(&Block::genesis(), 0);
+        let (a, b) = (child(&parent, 0), child(&parent, 1));
+        let grandchild = child(&a, 0);
+
+        assert!(pool.insert(a.clone()));
+        assert!(pool.insert(b.clone()));
+        assert!(!pool.insert(a.clone()));
+        assert!(pool.insert(grandchild.clone()));
+
+        assert_eq!(pool.take_children(&parent.blockhash), vec![a.clone(), b]);
+        assert!(pool.take_children(&parent.blockhash).is_empty());
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.take_children(&a.blockhash), vec![grandchild]);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn evicts_oldest_when_full() {
+        let mut pool = OrphanPool::new(2);
+        let parent = child(&Block::genesis(), 0);
+        let orphans: Vec<Block> = (0..3).map(|nonce| child(&parent, nonce)).collect();
+        orphans.iter().for_each(|orphan| {
+            pool.insert(orphan.clone());
+        });
+
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.contains(&orphans[0].blockhash));
+        assert_eq!(pool.take_children(&parent.blockhash), orphans[1..].to_vec());
+    }
+}
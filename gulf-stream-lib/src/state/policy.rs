@@ -0,0 +1,179 @@
+use ed25519_dalek::Keypair;
+
+use super::{
+    amount::Amount,
+    block::Block,
+    transaction::{Transaction, TransactionMessage},
+};
+use crate::{
+    ed25519::{publickey::PublicKey, signature::Signature},
+    err::GulfStreamError,
+};
+
+/// How coins come to exist : a reward for the miner of each block, halving on a fixed
+/// schedule, and mints reserved to the authorities declared with the chain. The miner also
+/// collects the gas paid in its block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonetaryPolicy {
+    /// Reward of the first block.
+    pub initial_reward: u64,
+    /// Number of blocks between two halvings of the reward, 0 to never halve it.
+    pub halving_interval: u64,
+    pub mint_authorities: Vec<PublicKey>,
+}
+
+impl Default for MonetaryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_reward: Self::DEFAULT_INITIAL_REWARD,
+            halving_interval: Self::DEFAULT_HALVING_INTERVAL,
+            mint_authorities: vec![],
+        }
+    }
+}
+
+impl MonetaryPolicy {
+    pub const DEFAULT_INITIAL_REWARD: u64 = 50;
+    pub const DEFAULT_HALVING_INTERVAL: u64 = 100_000;
+
+    /// Most the coinbase of the block at `index` may claim.
+    pub fn get_block_reward(&self, index: u64) -> u64 {
+        if index == 0 {
+            return 0;
+        }
+        let halvings = match self.halving_interval {
+            0 => 0,
+            interval => (index - 1) / interval,
+        };
+        self.initial_reward
+            .checked_shr(halvings as u32)
+            .unwrap_or(0)
+    }
+
+    /// What the coinbase of `block` claims : the block reward and the fees of the block.
+    pub fn get_coinbase_amount(&self, block: &Block) -> Result<Amount, GulfStreamError> {
+        Amount(self.get_block_reward(block.index)).checked_add(block.get_fees()?)
+    }
+
+    pub fn can_mint(&self, pk: &PublicKey) -> bool {
+        self.mint_authorities.contains(pk)
+    }
+
+    /// Reward transaction for `miner`, to put first in the block at `index` collecting `fees`.
+    pub fn create_coinbase(
+        &self,
+        miner: &Keypair,
+        chain_id: &str,
+        index: u64,
+        fees: Amount,
+    ) -> Result<Transaction, GulfStreamError> {
+        let msg = TransactionMessage::Coinbase {
+            amount: Amount(self.get_block_reward(index)).checked_add(fees)?,
+        };
+        Ok(Signature::sign_payload(
+            miner, chain_id, index, index, 0, 0, msg,
+        ))
+    }
+
+    /// A block starts with its only coinbase, which pays its producer exactly the reward at
+    /// its index and its fees, and mints only from authorities.
+    pub fn validate(&self, block: &Block) -> Result<(), GulfStreamError> {
+        if !matches!(block.transactions.first(), Some(tx) if tx.is_coinbase()) {
+            return Err(GulfStreamError::InvalidCoinbase);
+        }
+        let coinbase_amount = self.get_coinbase_amount(block)?;
+        for (position, tx) in block.transactions.iter().enumerate() {
+            match tx.msg {
+                TransactionMessage::Coinbase { amount }
+                    if position != 0
+                        || amount != coinbase_amount
+                        || tx.gas != 0
+                        || tx.valid_from != block.index
+                        || tx.valid_until != block.index =>
+                {
+                    return Err(GulfStreamError::InvalidCoinbase);
+                }
+                TransactionMessage::Mint { .. } if !self.can_mint(&tx.payer) => {
+                    return Err(GulfStreamError::UnauthorizedMint);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::genesis::Genesis;
+    use crate::utils::fixtures::keypair;
+
+    #[test]
+    fn halves_reward() {
+        let policy = MonetaryPolicy {
+            initial_reward: 50,
+            halving_interval: 10,
+            mint_authorities: vec![],
+        };
+
+        assert_eq!(policy.get_block_reward(0), 0);
+        assert_eq!(policy.get_block_reward(1), 50);
+        assert_eq!(policy.get_block_reward(10), 50);
+        assert_eq!(policy.get_block_reward(11), 25);
+        assert_eq!(policy.get_block_reward(21), 12);
+        assert_eq!(policy.get_block_reward(10 * 64 + 1), 0);
+    }
+
+    #[test]
+    fn validates_coinbase() {
+        let policy = MonetaryPolicy::default();
+        let miner = keypair();
+        let coinbase = policy
+            .create_coinbase(&miner, Genesis::DEFAULT_CHAIN_ID, 1, Amount::ZERO)
+            .unwrap();
+        let block = |transactions| Block {
+            index: 1,
+            transactions,
+            ..Default::default()
+        };
+
+        assert_eq!(policy.validate(&block(vec![coinbase.clone()])), Ok(()));
+        assert_eq!(
+            policy.validate(&block(vec![coinbase.clone(), coinbase.clone()])),
+            Err(GulfStreamError::InvalidCoinbase)
+        );
+        assert_eq!(
+            policy.validate(&block(vec![])),
+            Err(GulfStreamError::InvalidCoinbase)
+        );
+        let claiming = |amount| {
+            let msg = TransactionMessage::Coinbase {
+                amount: Amount(amount),
+            };
+            Signature::sign_payload(&miner, Genesis::DEFAULT_CHAIN_ID, 1, 1, 0, 0, msg)
+        };
+        for amount in [
+            policy.get_block_reward(1) - 1,
+            policy.get_block_reward(1) + 1,
+        ] {
+            assert_eq!(
+                policy.validate(&block(vec![claiming(amount)])),
+                Err(GulfStreamError::InvalidCoinbase)
+            );
+        }
+        let mint = Signature::sign_payload(
+            &miner,
+            Genesis::DEFAULT_CHAIN_ID,
+            1,
+            1,
+            0,
+            0,
+            TransactionMessage::Mint { amount: Amount(1) },
+        );
+        assert_eq!(
+            policy.validate(&block(vec![coinbase, mint])),
+            Err(GulfStreamError::UnauthorizedMint)
+        );
+    }
+}
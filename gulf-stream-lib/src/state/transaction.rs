@@ -1,18 +1,23 @@
 use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::ops::Add;
 
 use crate::ed25519::{publickey::PublicKey, signature::Signature};
 use crate::err::GulfStreamError;
 use crate::utils::serde::{BytesDeserialize, BytesSerialize};
 use ed25519_dalek::Verifier;
 
+use super::amount::Amount;
 use super::block::TransactionState;
 
 #[derive(Debug, Clone)]
 pub struct Transaction {
-    pub blockheight: u64,
+    /// First block index the transaction can be included in.
+    pub valid_from: u64,
+    /// Last block index the transaction can be included in.
+    pub valid_until: u64,
     pub gas: u64,
+    /// Sequence number of the payer : its count of transactions already on chain.
+    pub nonce: u64,
     pub msg: TransactionMessage,
     pub payer: PublicKey,
     pub signature: Signature,
@@ -23,11 +28,23 @@ impl Transaction {
         txs.iter().flat_map(|tx| tx.serialize()).collect()
     }
 
-    pub fn get_balance_deltas_from_txs(txs: &Vec<Self>) -> HashMap<PublicKey, BalanceDelta> {
-        txs.iter().fold(HashMap::new(), |mut res, tx| {
-            BalanceDelta::update_table(tx, &mut res);
-            res
-        })
+    /// Inverse of `get_raw_txs`.
+    pub fn from_raw_txs(mut raw_txs: &[u8]) -> Result<Vec<Self>, GulfStreamError> {
+        let mut txs = vec![];
+        while !raw_txs.is_empty() {
+            txs.push(Self::deserialize(&mut raw_txs)?);
+        }
+        Ok(txs)
+    }
+
+    pub fn get_balance_deltas_from_txs(
+        txs: &Vec<Self>,
+    ) -> Result<HashMap<PublicKey, BalanceDelta>, GulfStreamError> {
+        let mut res = HashMap::new();
+        for tx in txs.iter() {
+            BalanceDelta::update_table(tx, &mut res)?;
+        }
+        Ok(res)
     }
 
     pub fn get_involved_pk_from_txs(txs: &Vec<Self>) -> Vec<PublicKey> {
@@ -36,34 +53,57 @@ impl Transaction {
 }
 
 impl Transaction {
-    pub fn is_valid(&self, payer_balance: u64) -> bool {
-        self.sign_is_valid() && self.tx_msg_is_valid() && self.is_valid_for_payer(payer_balance)
+    /// Maximum number of blocks between `valid_from` and `valid_until`.
+    pub const MAX_VALIDITY_WINDOW: u64 = 100;
+
+    pub fn is_valid(&self, chain_id: &str, payer_balance: Amount) -> bool {
+        self.sign_is_valid(chain_id)
+            && self.tx_msg_is_valid()
+            && self.is_valid_for_payer(payer_balance)
     }
 
-    pub fn get_balance_deltas(&self) -> HashMap<PublicKey, BalanceDelta> {
+    pub fn get_balance_deltas(&self) -> Result<HashMap<PublicKey, BalanceDelta>, GulfStreamError> {
         let mut res = HashMap::new();
-        BalanceDelta::update_table(self, &mut res);
-        res
+        BalanceDelta::update_table(self, &mut res)?;
+        Ok(res)
     }
 
     pub fn get_involved_pk(&self) -> Vec<PublicKey> {
         match &self.msg {
-            TransactionMessage::Mint { .. } => vec![self.payer.clone()],
+            TransactionMessage::Mint { .. } | TransactionMessage::Coinbase { .. } => {
+                vec![self.payer.clone()]
+            }
             TransactionMessage::Transfer { to, .. } => vec![self.payer.clone(), to.clone()],
         }
     }
 
-    pub fn get_balance_delta_from_pk(&self, pk: &PublicKey) -> BalanceDelta {
+    pub fn get_balance_delta_from_pk(
+        &self,
+        pk: &PublicKey,
+    ) -> Result<BalanceDelta, GulfStreamError> {
         let raw_delta = match &self.msg {
             TransactionMessage::Mint { amount } if self.payer.eq(pk) => BalanceDelta::Pos(*amount),
+            TransactionMessage::Coinbase { amount } if self.payer.eq(pk) => {
+                BalanceDelta::Pos(*amount)
+            }
             TransactionMessage::Transfer { to, amount } if to.eq(pk) => BalanceDelta::Pos(*amount),
             TransactionMessage::Transfer { to, amount } if to.ne(pk) => BalanceDelta::Neg(*amount),
             _ => Default::default(),
         };
         if self.payer.eq(pk) {
-            raw_delta.add(BalanceDelta::Neg(self.gas))
+            raw_delta.checked_add(BalanceDelta::Neg(Amount(self.gas)))
         } else {
-            raw_delta
+            Ok(raw_delta)
+        }
+    }
+
+    /// Most the payer may be debited : the gas and what it transfers.
+    pub fn get_cost(&self) -> Result<Amount, GulfStreamError> {
+        match &self.msg {
+            TransactionMessage::Mint { .. } | TransactionMessage::Coinbase { .. } => {
+                Ok(Amount(self.gas))
+            }
+            TransactionMessage::Transfer { amount, .. } => amount.checked_add(Amount(self.gas)),
         }
     }
 
@@ -77,38 +117,56 @@ impl Transaction {
 
     pub fn serialize_content(&self) -> Vec<u8> {
         let mut vec = vec![];
-        vec.extend(self.blockheight.serialize());
+        vec.extend(self.valid_from.serialize());
+        vec.extend(self.valid_until.serialize());
         vec.extend(self.gas.serialize());
+        vec.extend(self.nonce.serialize());
         vec.extend(self.msg.serialize());
         vec
     }
 
-    pub fn sign_is_valid(&self) -> bool {
-        let mut message = vec![];
-        message.extend(self.blockheight.serialize());
-        message.extend(self.gas.serialize());
-        message.extend(self.msg.serialize());
-        let encoded = bs58::encode(message).into_vec();
+    /// What the payer signs : the content, bound to the network `chain_id` names so the
+    /// signature is worthless on any other.
+    pub fn get_signed_payload(&self, chain_id: &str) -> Vec<u8> {
+        let mut vec = chain_id.as_bytes().to_vec().serialize();
+        vec.extend(self.serialize_content());
+        bs58::encode(vec).into_vec()
+    }
+
+    pub fn sign_is_valid(&self, chain_id: &str) -> bool {
         self.payer
             .0
-            .verify(encoded.as_slice(), &self.signature.0)
+            .verify(&self.get_signed_payload(chain_id), &self.signature.0)
             .is_ok()
     }
 
+    /// A coinbase is paid by nobody, it leaves the nonce of its miner alone.
+    pub fn is_coinbase(&self) -> bool {
+        matches!(self.msg, TransactionMessage::Coinbase { .. })
+    }
+
+    pub fn window_is_valid(&self) -> bool {
+        self.valid_from <= self.valid_until
+            && self.valid_until - self.valid_from <= Self::MAX_VALIDITY_WINDOW
+    }
+
+    pub fn can_be_included_at(&self, index: u64) -> bool {
+        self.valid_from <= index && index <= self.valid_until
+    }
+
+    pub fn is_expired_at(&self, index: u64) -> bool {
+        self.valid_until < index
+    }
+
     pub fn tx_msg_is_valid(&self) -> bool {
         match &self.msg {
-            TransactionMessage::Mint { .. } => true,
+            TransactionMessage::Mint { .. } | TransactionMessage::Coinbase { .. } => true,
             TransactionMessage::Transfer { to, .. } => self.payer.ne(to),
         }
     }
 
-    fn is_valid_for_payer(&self, payer_balance: u64) -> bool {
-        match &self.msg {
-            TransactionMessage::Mint { .. } => payer_balance.ge(&self.gas),
-            TransactionMessage::Transfer { to: _, amount } => {
-                payer_balance.ge(&(*amount + self.gas))
-            }
-        }
+    fn is_valid_for_payer(&self, payer_balance: Amount) -> bool {
+        matches!(self.get_cost(), Ok(cost) if payer_balance.ge(&cost))
     }
 
     pub fn into_tx_state(self) -> TransactionState {
@@ -135,8 +193,10 @@ impl BytesSerialize for Transaction {
 impl BytesDeserialize for Transaction {
     fn deserialize(buf: &mut &[u8]) -> Result<Self, GulfStreamError> {
         Ok(Self {
-            blockheight: u64::deserialize(buf)?,
+            valid_from: u64::deserialize(buf)?,
+            valid_until: u64::deserialize(buf)?,
             gas: u64::deserialize(buf)?,
+            nonce: u64::deserialize(buf)?,
             msg: TransactionMessage::deserialize(buf)?,
             payer: PublicKey::deserialize(buf)?,
             signature: Signature::deserialize(buf)?,
@@ -152,8 +212,10 @@ impl PartialOrd for Transaction {
 
 impl PartialEq for Transaction {
     fn eq(&self, other: &Self) -> bool {
-        self.blockheight == other.blockheight
+        self.valid_from == other.valid_from
+            && self.valid_until == other.valid_until
             && self.gas == other.gas
+            && self.nonce == other.nonce
             && self.msg == other.msg
             && self.payer == other.payer
             && self.signature == other.signature
@@ -170,13 +232,23 @@ impl Ord for Transaction {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum TransactionMessage {
-    Mint { amount: u64 },
-    Transfer { to: PublicKey, amount: u64 },
+    /// Only for the mint authorities of the chain.
+    Mint {
+        amount: Amount,
+    },
+    Transfer {
+        to: PublicKey,
+        amount: Amount,
+    },
+    /// Block reward, first transaction of a block and never in the mem pool.
+    Coinbase {
+        amount: Amount,
+    },
 }
 
 impl Default for TransactionMessage {
     fn default() -> Self {
-        Self::Mint { amount: 77 }
+        Self::Mint { amount: Amount(77) }
     }
 }
 
@@ -194,6 +266,11 @@ impl BytesSerialize for TransactionMessage {
                 vec.extend(amount.serialize());
                 return vec;
             }
+            TransactionMessage::Coinbase { amount } => {
+                let mut vec = vec![2];
+                vec.extend(amount.serialize());
+                return vec;
+            }
         }
     }
 }
@@ -205,13 +282,18 @@ impl BytesDeserialize for TransactionMessage {
         match index {
             0 => {
                 return Ok(Self::Mint {
-                    amount: u64::deserialize(value)?,
+                    amount: Amount::deserialize(value)?,
                 });
             }
             1 => {
                 return Ok(Self::Transfer {
                     to: PublicKey::deserialize(value)?,
-                    amount: u64::deserialize(value)?,
+                    amount: Amount::deserialize(value)?,
+                });
+            }
+            2 => {
+                return Ok(Self::Coinbase {
+                    amount: Amount::deserialize(value)?,
                 });
             }
             _ => Err(crate::err::GulfStreamError::Default),
@@ -221,39 +303,82 @@ impl BytesDeserialize for TransactionMessage {
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum BalanceDelta {
-    Pos(u64),
-    Neg(u64),
+    Pos(Amount),
+    Neg(Amount),
 }
 
 impl BalanceDelta {
     pub fn to_u64(self) -> Option<u64> {
         match self {
-            BalanceDelta::Pos(a) => Some(a),
+            BalanceDelta::Pos(a) => Some(a.0),
             BalanceDelta::Neg(_) => None,
         }
     }
 
-    pub fn update_table(tx: &Transaction, table: &mut HashMap<PublicKey, BalanceDelta>) {
-        let involved_pks = tx.get_involved_pk();
-        involved_pks.iter().for_each(|pk| {
-            table
-                .entry(pk.clone())
-                .and_modify(|e: &mut BalanceDelta| *e = e.add(tx.get_balance_delta_from_pk(pk)))
-                .or_insert(tx.get_balance_delta_from_pk(pk));
-        });
+    pub fn update_table(
+        tx: &Transaction,
+        table: &mut HashMap<PublicKey, BalanceDelta>,
+    ) -> Result<(), GulfStreamError> {
+        for pk in tx.get_involved_pk() {
+            let delta = tx.get_balance_delta_from_pk(&pk)?;
+            let entry = table.entry(pk).or_default();
+            *entry = entry.checked_add(delta)?;
+        }
+        Ok(())
     }
 
     pub fn is_positive_or_nil(&self) -> bool {
         match self {
             BalanceDelta::Pos(_) => true,
-            BalanceDelta::Neg(a) => 0.eq(a),
+            BalanceDelta::Neg(a) => a.is_zero(),
         }
     }
+
+    /// Fails rather than wrapping when the sum does not fit in an amount.
+    pub fn checked_add(self, rhs: Self) -> Result<Self, GulfStreamError> {
+        Ok(match (self, rhs) {
+            (BalanceDelta::Pos(a), BalanceDelta::Pos(b)) => Self::Pos(a.checked_add(b)?),
+            (BalanceDelta::Pos(a), BalanceDelta::Neg(b)) if a > b => Self::Pos(a.checked_sub(b)?),
+            (BalanceDelta::Pos(a), BalanceDelta::Neg(b)) => Self::Neg(b.checked_sub(a)?),
+            (BalanceDelta::Neg(a), BalanceDelta::Pos(b)) if a > b => Self::Neg(a.checked_sub(b)?),
+            (BalanceDelta::Neg(a), BalanceDelta::Pos(b)) => Self::Pos(b.checked_sub(a)?),
+            (BalanceDelta::Neg(a), BalanceDelta::Neg(b)) => Self::Neg(a.checked_add(b)?),
+        })
+    }
+}
+
+impl BytesSerialize for BalanceDelta {
+    fn serialize(&self) -> Vec<u8> {
+        let mut vec = vec![];
+        match self {
+            BalanceDelta::Pos(a) => {
+                vec.extend(true.serialize());
+                vec.extend(a.serialize());
+            }
+            BalanceDelta::Neg(a) => {
+                vec.extend(false.serialize());
+                vec.extend(a.serialize());
+            }
+        }
+        vec
+    }
+}
+
+impl BytesDeserialize for BalanceDelta {
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, GulfStreamError> {
+        let is_positive = bool::deserialize(buf)?;
+        let amount = Amount::deserialize(buf)?;
+        Ok(if is_positive {
+            Self::Pos(amount)
+        } else {
+            Self::Neg(amount)
+        })
+    }
 }
 
 impl Default for BalanceDelta {
     fn default() -> Self {
-        Self::Pos(0)
+        Self::Pos(Amount::ZERO)
     }
 }
 
@@ -262,10 +387,10 @@ impl PartialOrd for BalanceDelta {
         match (self, other) {
             (BalanceDelta::Pos(a), BalanceDelta::Pos(b)) => a.partial_cmp(b),
             (BalanceDelta::Neg(a), BalanceDelta::Neg(b)) => b.partial_cmp(a),
-            (BalanceDelta::Pos(a), BalanceDelta::Neg(b)) if *a != 0 && *b != 0 => {
+            (BalanceDelta::Pos(a), BalanceDelta::Neg(b)) if !a.is_zero() && !b.is_zero() => {
                 Some(Ordering::Greater)
             }
-            (BalanceDelta::Neg(a), BalanceDelta::Pos(b)) if *a != 0 && *b != 0 => {
+            (BalanceDelta::Neg(a), BalanceDelta::Pos(b)) if !a.is_zero() && !b.is_zero() => {
                 Some(Ordering::Less)
             }
             _ => Some(Ordering::Equal),
@@ -273,25 +398,20 @@ impl PartialOrd for BalanceDelta {
     }
 }
 
-impl Add for BalanceDelta {
-    type Output = Self;
-
-    fn add(self, rhs: Self) -> Self::Output {
-        match (self, rhs) {
-            (BalanceDelta::Pos(a), BalanceDelta::Pos(b)) => Self::Pos(a + b),
-            (BalanceDelta::Pos(a), BalanceDelta::Neg(b)) if a > b => Self::Pos(a - b),
-            (BalanceDelta::Pos(a), BalanceDelta::Neg(b)) => Self::Neg(b - a),
-            (BalanceDelta::Neg(a), BalanceDelta::Pos(b)) if a > b => Self::Neg(a - b),
-            (BalanceDelta::Neg(a), BalanceDelta::Pos(b)) => Self::Pos(b - a),
-            (BalanceDelta::Neg(a), BalanceDelta::Neg(b)) => Self::Neg(a + b),
-        }
-    }
-}
-
 #[cfg(test)]
 mod test {
 
     use super::*;
+    use crate::utils::fixtures::keypair;
+
+    #[test]
+    fn binds_signature_to_chain() {
+        let signer = keypair();
+        let tx = Signature::sign_payload(&signer, "testnet", 1, 2, 0, 0, Default::default());
+
+        assert!(tx.sign_is_valid("testnet"));
+        assert!(!tx.sign_is_valid("mainnet"));
+    }
 
     #[test]
     fn se_de_txm() {
@@ -310,22 +430,76 @@ mod test {
         .unwrap();
     }
 
+    #[test]
+    fn se_de_raw_txs() {
+        let txs = vec![
+            Transaction {
+                valid_from: 1,
+                valid_until: 2,
+                gas: 3,
+                nonce: 4,
+                msg: TransactionMessage::default(),
+                payer: PublicKey::random(),
+                signature: Default::default(),
+            },
+            Transaction {
+                valid_from: 5,
+                valid_until: 6,
+                gas: 7,
+                nonce: 8,
+                msg: TransactionMessage::Transfer {
+                    to: PublicKey::random(),
+                    amount: Amount(9),
+                },
+                payer: PublicKey::random(),
+                signature: Default::default(),
+            },
+        ];
+        let raw_txs = Transaction::get_raw_txs(&txs);
+        assert_eq!(Transaction::from_raw_txs(&raw_txs).unwrap(), txs);
+        assert!(Transaction::from_raw_txs(&[]).unwrap().is_empty());
+    }
+
     mod balance_delta {
         use super::*;
 
         #[test]
         fn add() {
-            let a = BalanceDelta::Pos(10);
-            let b = BalanceDelta::Neg(5);
+            let a = BalanceDelta::Pos(Amount(10));
+            let b = BalanceDelta::Neg(Amount(5));
+
+            assert_eq!(a.checked_add(b), Ok(BalanceDelta::Pos(Amount(5))));
+            assert_eq!(b.checked_add(a), Ok(BalanceDelta::Pos(Amount(5))));
 
-            assert_eq!(a.add(b), BalanceDelta::Pos(5));
-            assert_eq!(b.add(a), BalanceDelta::Pos(5));
+            let a = BalanceDelta::Pos(Amount(5));
+            let b = BalanceDelta::Neg(Amount(10));
 
-            let a = BalanceDelta::Pos(5);
-            let b = BalanceDelta::Neg(10);
+            assert_eq!(a.checked_add(b), Ok(BalanceDelta::Neg(Amount(5))));
+            assert_eq!(b.checked_add(a), Ok(BalanceDelta::Neg(Amount(5))));
+        }
 
-            assert_eq!(a.add(b), BalanceDelta::Neg(5));
-            assert_eq!(b.add(a), BalanceDelta::Neg(5));
+        #[test]
+        fn rejects_overflow() {
+            let max = BalanceDelta::Pos(Amount(u64::MAX));
+            assert_eq!(
+                max.checked_add(BalanceDelta::Pos(Amount(1))),
+                Err(GulfStreamError::AmountOverflow)
+            );
+
+            let tx = Transaction {
+                valid_from: 0,
+                valid_until: 0,
+                gas: 1,
+                nonce: 0,
+                msg: TransactionMessage::Transfer {
+                    to: PublicKey::random(),
+                    amount: Amount(u64::MAX),
+                },
+                payer: PublicKey::random(),
+                signature: Default::default(),
+            };
+            assert_eq!(tx.get_cost(), Err(GulfStreamError::AmountOverflow));
+            assert!(!tx.is_valid_for_payer(Amount(u64::MAX)));
         }
     }
 }
@@ -2,13 +2,24 @@ use pg_embed::pg_enums::PgAuthMethod;
 use pg_embed::pg_fetch::{PgFetchSettings, PG_V13};
 use pg_embed::postgres::{PgEmbed, PgSettings};
 use sqlx::{Pool, Postgres, Row};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::time::Duration;
 
+use super::history::{
+    Direction, HistoryCursor, HistoryEntry, HistoryPage, HistoryQuery, Involvement,
+};
+use super::migrations::{MIGRATIONS, SCHEMA_VERSION_TABLE};
+use super::store::Store;
 use crate::ed25519::publickey::PublicKey;
 use crate::ed25519::signature::Signature;
 use crate::err::GulfStreamError;
-use crate::state::transaction::Transaction;
+use crate::state::account::Account;
+use crate::state::amount::Amount;
+use crate::state::block::Block;
+use crate::state::blockchain::ChainUpdate;
+use crate::state::blockhash::Blockhash;
+use crate::state::transaction::{BalanceDelta, Transaction, TransactionMessage};
 use sqlx::postgres::{PgPoolOptions, PgRow};
 
 pub struct DbClient {
@@ -16,6 +27,12 @@ pub struct DbClient {
 }
 
 impl DbClient {
+    const HEAD_KEY: &str = "head";
+    const HISTORY_HEAD_KEY: &str = "history_head";
+    const MINT_KIND: i16 = 0;
+    const TRANSFER_KIND: i16 = 1;
+    const COINBASE_KIND: i16 = 2;
+
     pub async fn new(uri: String) -> Result<Self, GulfStreamError> {
         let pool = PgPoolOptions::new()
             .max_connections(5)
@@ -46,48 +63,391 @@ impl DbClient {
         Ok(pg)
     }
 
-    pub async fn init_tables(&self) -> Result<(), GulfStreamError> {
+    /// Brings the schema to the latest version, each migration in its own transaction.
+    pub async fn migrate(&self) -> Result<(), GulfStreamError> {
+        sqlx::query(SCHEMA_VERSION_TABLE)
+            .execute(&self.client)
+            .await?;
+        let current_version: i64 =
+            sqlx::query("SELECT COALESCE(MAX(version), 0) AS version FROM schema_version ;")
+                .fetch_one(&self.client)
+                .await?
+                .get("version");
+        let latest_version = MIGRATIONS.last().map_or(0, |migration| migration.version);
+        if current_version > latest_version {
+            return Err(GulfStreamError::SchemaTooNew(current_version));
+        }
+        for migration in MIGRATIONS
+            .iter()
+            .filter(|migration| migration.version > current_version)
+        {
+            let mut db_tx = self.client.begin().await?;
+            for statement in migration.statements {
+                sqlx::query(statement).execute(&mut db_tx).await?;
+            }
+            sqlx::query("INSERT INTO schema_version (version,name) VALUES ($1,$2);")
+                .bind(migration.version)
+                .bind(migration.name)
+                .execute(&mut db_tx)
+                .await?;
+            db_tx.commit().await?;
+            println!(
+                "Applied migration {} : {}",
+                migration.version, migration.name
+            );
+        }
+        Ok(())
+    }
+
+    fn map_row_to_block(row: PgRow, transactions: Vec<Transaction>) -> Block {
+        let blockheight: i64 = row.get(&"blockheight");
+        let nonce: i64 = row.get(&"nonce");
+        let difficulty: i32 = row.get(&"difficulty");
+        let timestamp: i64 = row.get(&"timestamp");
+        Block {
+            index: blockheight as u64,
+            blockhash: Blockhash(row.get(&"blockhash")),
+            previous_blockhash: Blockhash(row.get(&"previous_blockhash")),
+            transactions,
+            nonce: nonce as u64,
+            difficulty: difficulty as u32,
+            timestamp: timestamp as u64,
+        }
+    }
+
+    fn map_row_to_tx(row: &PgRow) -> Result<Transaction, GulfStreamError> {
+        let valid_from: i64 = row.get(&"valid_from");
+        let valid_until: i64 = row.get(&"valid_until");
+        let gas: i64 = row.get(&"gas");
+        let nonce: i64 = row.get(&"nonce");
+        let payer: &str = row.get(&"payer");
+        let signature: &str = row.get(&"signature");
+        Ok(Transaction {
+            valid_from: valid_from as u64,
+            valid_until: valid_until as u64,
+            gas: gas as u64,
+            nonce: nonce as u64,
+            msg: Self::map_row_to_msg(row)?,
+            payer: PublicKey::try_from_str(payer)?,
+            signature: Signature::try_from_str(signature)?,
+        })
+    }
+
+    fn map_row_to_msg(row: &PgRow) -> Result<TransactionMessage, GulfStreamError> {
+        let msg_kind: i16 = row.get(&"msg_kind");
+        let msg_to: Option<&str> = row.get(&"msg_to");
+        let msg_amount: i64 = row.get(&"msg_amount");
+        match (msg_kind, msg_to) {
+            (Self::MINT_KIND, _) => Ok(TransactionMessage::Mint {
+                amount: Amount(msg_amount as u64),
+            }),
+            (Self::TRANSFER_KIND, Some(to)) => Ok(TransactionMessage::Transfer {
+                to: PublicKey::try_from_str(to)?,
+                amount: Amount(msg_amount as u64),
+            }),
+            (Self::COINBASE_KIND, _) => Ok(TransactionMessage::Coinbase {
+                amount: Amount(msg_amount as u64),
+            }),
+            _ => Err(GulfStreamError::SerDeError("TransactionMessage".into())),
+        }
+    }
+
+    async fn insert_tx_with(
+        tx: &Transaction,
+        db_tx: &mut sqlx::Transaction<'_, Postgres>,
+    ) -> Result<(), GulfStreamError> {
+        let (msg_kind, msg_to, msg_amount) = match &tx.msg {
+            TransactionMessage::Mint { amount } => (Self::MINT_KIND, None, amount),
+            TransactionMessage::Coinbase { amount } => (Self::COINBASE_KIND, None, amount),
+            TransactionMessage::Transfer { to, amount } => {
+                (Self::TRANSFER_KIND, Some(to.into_string()), amount)
+            }
+        };
         sqlx::query(
-            "CREATE TABLE blocks ( blockheight bigint primary key, blockhash varchar(40) NOT NULL );"
-        ).execute(&self.client)
+            "INSERT INTO transactions (signature,valid_from,valid_until,gas,nonce,payer,msg_kind,msg_to,msg_amount) VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9) ON CONFLICT DO NOTHING;",
+        )
+        .bind(tx.signature.into_string())
+        .bind(tx.valid_from as i64)
+        .bind(tx.valid_until as i64)
+        .bind(tx.gas as i64)
+        .bind(tx.nonce as i64)
+        .bind(tx.payer.into_string())
+        .bind(msg_kind)
+        .bind(msg_to)
+        .bind(msg_amount.0 as i64)
+        .execute(&mut *db_tx)
         .await?;
+        for involvement in Involvement::from_tx(tx) {
+            sqlx::query(
+                "INSERT INTO address_transactions (address,signature,sent,received) VALUES ($1,$2,$3,$4) ON CONFLICT DO NOTHING;",
+            )
+            .bind(involvement.pk.into_string())
+            .bind(tx.signature.into_string())
+            .bind(involvement.sent)
+            .bind(involvement.received)
+            .execute(&mut *db_tx)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn set_canonical(
+        blocks: &[Block],
+        canonical: bool,
+        db_tx: &mut sqlx::Transaction<'_, Postgres>,
+    ) -> Result<(), GulfStreamError> {
+        let blockhashes: Vec<&[u8]> = blocks
+            .iter()
+            .map(|block| block.blockhash.as_ref())
+            .collect();
+        sqlx::query("UPDATE blocks SET canonical = $1 WHERE blockhash = ANY($2) ;")
+            .bind(canonical)
+            .bind(blockhashes)
+            .execute(&mut *db_tx)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_metadata<'c, E>(
+        key: &str,
+        value: &[u8],
+        executor: E,
+    ) -> Result<(), GulfStreamError>
+    where
+        E: sqlx::Executor<'c, Database = Postgres>,
+    {
         sqlx::query(
-            "CREATE TABLE txs ( signature varchar(92) primary key NOT NULL, blockheight bigint, gas bigint, payer varchar(44));",
-        ).execute(&self.client)
+            "INSERT INTO chain_metadata (key,value) VALUES ($1,$2) ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value;",
+        )
+        .bind(key)
+        .bind(value)
+        .execute(executor)
         .await?;
         Ok(())
     }
 
-    pub async fn get_tx(&self, tx: &Signature) -> Result<Transaction, GulfStreamError> {
-        let row = sqlx::query("SELECT * FROM txs WHERE signature = $1 ;")
-            .bind(tx.into_string())
-            .fetch_one(&self.client)
+    async fn get_metadata(&self, key: &str) -> Result<Option<Vec<u8>>, GulfStreamError> {
+        let row = sqlx::query("SELECT value FROM chain_metadata WHERE key = $1 ;")
+            .bind(key)
+            .fetch_optional(&self.client)
             .await?;
-        Self::map_row_to_tx(row)
+        Ok(row.map(|row| row.get("value")))
     }
+}
 
-    pub async fn insert_tx(&self, tx: &Transaction) -> Result<(), GulfStreamError> {
-        sqlx::query("INSERT INTO txs (signature,blockheight,gas,payer) VALUES ($1,$2,$3,$4);")
+#[tonic::async_trait]
+impl Store for DbClient {
+    async fn insert_block(&self, block: &Block) -> Result<(), GulfStreamError> {
+        let mut db_tx = self.client.begin().await?;
+        let inserted = sqlx::query(
+            "INSERT INTO blocks (blockhash,blockheight,previous_blockhash,nonce,difficulty,timestamp) VALUES ($1,$2,$3,$4,$5,$6) ON CONFLICT DO NOTHING;",
+        )
+        .bind(block.blockhash.as_ref())
+        .bind(block.index as i64)
+        .bind(block.previous_blockhash.as_ref())
+        .bind(block.nonce as i64)
+        .bind(block.difficulty as i32)
+        .bind(block.timestamp as i64)
+        .execute(&mut db_tx)
+        .await?
+        .rows_affected();
+        if inserted == 0 {
+            return Ok(());
+        }
+        for (position, tx) in block.transactions.iter().enumerate() {
+            Self::insert_tx_with(tx, &mut db_tx).await?;
+            sqlx::query(
+                "INSERT INTO block_transactions (blockhash,position,signature) VALUES ($1,$2,$3);",
+            )
+            .bind(block.blockhash.as_ref())
+            .bind(position as i32)
             .bind(tx.signature.into_string())
-            .bind(tx.blockheight as i64)
-            .bind(tx.gas as i64)
-            .bind(tx.payer.into_string())
-            .execute(&self.client)
+            .execute(&mut db_tx)
             .await?;
+        }
+        db_tx.commit().await?;
         Ok(())
     }
 
-    fn map_row_to_tx(row: PgRow) -> Result<Transaction, GulfStreamError> {
-        let blockheight: i64 = row.get(&"blockheight");
-        let gas: i64 = row.get(&"gas");
-        let payer: &str = row.get(&"payer");
-        let signature: &str = row.get(&"signature");
-        Ok(Transaction {
-            blockheight: blockheight as u64,
-            gas: gas as u64,
-            msg: Default::default(),
-            payer: PublicKey::try_from_str(payer)?,
-            signature: Signature::try_from_str(signature)?,
+    async fn get_blocks(&self) -> Result<Vec<Block>, GulfStreamError> {
+        let mut transactions: HashMap<Vec<u8>, Vec<Transaction>> = HashMap::new();
+        for row in sqlx::query(
+            "SELECT block_transactions.blockhash, transactions.* FROM block_transactions JOIN transactions ON transactions.signature = block_transactions.signature ORDER BY block_transactions.blockhash, block_transactions.position ;",
+        )
+        .fetch_all(&self.client)
+        .await?
+        {
+            transactions
+                .entry(row.get(&"blockhash"))
+                .or_default()
+                .push(Self::map_row_to_tx(&row)?);
+        }
+        Ok(sqlx::query("SELECT * FROM blocks ORDER BY blockheight ;")
+            .fetch_all(&self.client)
+            .await?
+            .into_iter()
+            .map(|row| {
+                let blockhash: Vec<u8> = row.get(&"blockhash");
+                let block_txs = transactions.remove(&blockhash).unwrap_or_default();
+                Self::map_row_to_block(row, block_txs)
+            })
+            .collect())
+    }
+
+    async fn get_head(&self) -> Result<Option<Blockhash>, GulfStreamError> {
+        Ok(self.get_metadata(Self::HEAD_KEY).await?.map(Blockhash))
+    }
+
+    async fn set_head(&self, blockhash: &Blockhash) -> Result<(), GulfStreamError> {
+        Self::set_metadata(Self::HEAD_KEY, blockhash.as_ref(), &self.client).await
+    }
+
+    async fn insert_tx(&self, tx: &Transaction) -> Result<(), GulfStreamError> {
+        let mut db_tx = self.client.begin().await?;
+        Self::insert_tx_with(tx, &mut db_tx).await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_tx(&self, signature: &Signature) -> Result<Option<Transaction>, GulfStreamError> {
+        sqlx::query("SELECT * FROM transactions WHERE signature = $1 ;")
+            .bind(signature.into_string())
+            .fetch_optional(&self.client)
+            .await?
+            .map(|row| Self::map_row_to_tx(&row))
+            .transpose()
+    }
+    async fn set_accounts(
+        &self,
+        accounts: &HashMap<PublicKey, Account>,
+    ) -> Result<(), GulfStreamError> {
+        let mut db_tx = self.client.begin().await?;
+        for (pk, account) in accounts.iter() {
+            sqlx::query(
+                "INSERT INTO accounts (publickey,balance,nonce) VALUES ($1,$2,$3) ON CONFLICT (publickey) DO UPDATE SET balance = EXCLUDED.balance, nonce = EXCLUDED.nonce;",
+            )
+            .bind(pk.into_string())
+            .bind(match account.balance {
+                BalanceDelta::Pos(amount) => i64::try_from(amount.0),
+                BalanceDelta::Neg(amount) => i64::try_from(amount.0).map(|amount| -amount),
+            }
+            .map_err(|_| GulfStreamError::AmountOverflow)?)
+            .bind(account.nonce as i64)
+            .execute(&mut db_tx)
+            .await?;
+        }
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_account(&self, pk: &PublicKey) -> Result<Option<Account>, GulfStreamError> {
+        let row = sqlx::query("SELECT * FROM accounts WHERE publickey = $1 ;")
+            .bind(pk.into_string())
+            .fetch_optional(&self.client)
+            .await?;
+        Ok(row.map(|row| {
+            let balance: i64 = row.get(&"balance");
+            let nonce: i64 = row.get(&"nonce");
+            Account {
+                balance: if balance < 0 {
+                    BalanceDelta::Neg(Amount(balance.unsigned_abs()))
+                } else {
+                    BalanceDelta::Pos(Amount(balance as u64))
+                },
+                nonce: nonce as u64,
+            }
+        }))
+    }
+
+    async fn index_history(
+        &self,
+        update: &ChainUpdate,
+        head: &Blockhash,
+    ) -> Result<(), GulfStreamError> {
+        let mut db_tx = self.client.begin().await?;
+        Self::set_canonical(&update.disconnected, false, &mut db_tx).await?;
+        Self::set_canonical(&update.connected, true, &mut db_tx).await?;
+        Self::set_metadata(Self::HISTORY_HEAD_KEY, head.as_ref(), &mut db_tx).await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_history_head(&self) -> Result<Option<Blockhash>, GulfStreamError> {
+        Ok(self
+            .get_metadata(Self::HISTORY_HEAD_KEY)
+            .await?
+            .map(Blockhash))
+    }
+
+    async fn clear_history(&self) -> Result<(), GulfStreamError> {
+        let mut db_tx = self.client.begin().await?;
+        sqlx::query("UPDATE blocks SET canonical = false WHERE canonical ;")
+            .execute(&mut db_tx)
+            .await?;
+        sqlx::query("DELETE FROM chain_metadata WHERE key = $1 ;")
+            .bind(Self::HISTORY_HEAD_KEY)
+            .execute(&mut db_tx)
+            .await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_history(&self, query: &HistoryQuery) -> Result<HistoryPage, GulfStreamError> {
+        let upper_bound = match query.get_upper_bound() {
+            Some(upper_bound) => upper_bound,
+            None => return Ok(HistoryPage::default()),
+        };
+        let lower_bound = query.get_lower_bound();
+        let limit = query.get_limit();
+        let rows = sqlx::query(
+            "SELECT transactions.*, blocks.blockheight, block_transactions.position FROM block_transactions JOIN blocks ON blocks.blockhash = block_transactions.blockhash JOIN transactions ON transactions.signature = block_transactions.signature WHERE blocks.canonical AND (blocks.blockheight, block_transactions.position::bigint) >= ($1,$2) AND (blocks.blockheight, block_transactions.position::bigint) <= ($3,$4) AND ($5::varchar IS NULL OR EXISTS (SELECT 1 FROM address_transactions WHERE address_transactions.signature = block_transactions.signature AND address_transactions.address = $5 AND (address_transactions.sent OR NOT $6) AND (address_transactions.received OR NOT $7))) ORDER BY blocks.blockheight DESC, block_transactions.position DESC LIMIT $8 ;",
+        )
+        .bind(lower_bound.block_index.min(i64::MAX as u64) as i64)
+        .bind(lower_bound.position as i64)
+        .bind(upper_bound.block_index.min(i64::MAX as u64) as i64)
+        .bind(upper_bound.position as i64)
+        .bind(query.address.as_ref().map(PublicKey::into_string))
+        .bind(query.direction == Direction::Sent)
+        .bind(query.direction == Direction::Received)
+        .bind(limit as i64 + 1)
+        .fetch_all(&self.client)
+        .await?;
+        let entries = rows.iter().map(|row| {
+            let blockheight: i64 = row.get(&"blockheight");
+            let position: i32 = row.get(&"position");
+            Ok(HistoryEntry {
+                cursor: HistoryCursor {
+                    block_index: blockheight as u64,
+                    position: position as u32,
+                },
+                tx: Self::map_row_to_tx(row)?,
+            })
+        });
+        HistoryPage::collect(entries, limit)
+    }
+
+    async fn get_tx_in_history(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<(Blockhash, HistoryEntry)>, GulfStreamError> {
+        let row = sqlx::query(
+            "SELECT transactions.*, blocks.blockhash, blocks.blockheight, block_transactions.position FROM block_transactions JOIN blocks ON blocks.blockhash = block_transactions.blockhash JOIN transactions ON transactions.signature = block_transactions.signature WHERE blocks.canonical AND block_transactions.signature = $1 ;",
+        )
+        .bind(signature.into_string())
+        .fetch_optional(&self.client)
+        .await?;
+        row.map(|row| {
+            let blockheight: i64 = row.get(&"blockheight");
+            let position: i32 = row.get(&"position");
+            let entry = HistoryEntry {
+                cursor: HistoryCursor {
+                    block_index: blockheight as u64,
+                    position: position as u32,
+                },
+                tx: Self::map_row_to_tx(&row)?,
+            };
+            Ok((Blockhash(row.get(&"blockhash")), entry))
         })
+        .transpose()
     }
 }
@@ -0,0 +1,272 @@
+use crate::ed25519::publickey::PublicKey;
+use crate::err::GulfStreamError;
+use crate::state::block::Block;
+use crate::state::transaction::{Transaction, TransactionMessage};
+use crate::utils::serde::{BytesDeserialize, BytesSerialize};
+
+/// Side of the transfer the filtered address is on.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Direction {
+    #[default]
+    Any,
+    /// The address paid the transaction.
+    Sent,
+    /// The address was credited, by a transfer or its own mint.
+    Received,
+}
+
+impl Direction {
+    pub fn matches(&self, involvement: &Involvement) -> bool {
+        match self {
+            Direction::Any => true,
+            Direction::Sent => involvement.sent,
+            Direction::Received => involvement.received,
+        }
+    }
+}
+
+/// How a transaction touches an address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Involvement {
+    pub pk: PublicKey,
+    pub sent: bool,
+    pub received: bool,
+}
+
+impl Involvement {
+    pub fn from_tx(tx: &Transaction) -> Vec<Involvement> {
+        let mut involvements = vec![match tx.msg {
+            TransactionMessage::Mint { .. } => Involvement {
+                pk: tx.payer.clone(),
+                sent: true,
+                received: true,
+            },
+            TransactionMessage::Coinbase { .. } => Involvement {
+                pk: tx.payer.clone(),
+                sent: false,
+                received: true,
+            },
+            TransactionMessage::Transfer { .. } => Involvement {
+                pk: tx.payer.clone(),
+                sent: true,
+                received: false,
+            },
+        }];
+        if let TransactionMessage::Transfer { to, .. } = &tx.msg {
+            if to.eq(&tx.payer) {
+                involvements[0].received = true;
+            } else {
+                involvements.push(Involvement {
+                    pk: to.clone(),
+                    sent: false,
+                    received: true,
+                });
+            }
+        }
+        involvements
+    }
+}
+
+/// Place of a transaction in the canonical chain, history goes by decreasing position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HistoryCursor {
+    pub block_index: u64,
+    pub position: u32,
+}
+
+impl HistoryCursor {
+    /// Position right before this one, if any.
+    pub fn previous(&self) -> Option<HistoryCursor> {
+        match (self.block_index, self.position) {
+            (0, 0) => None,
+            (block_index, 0) => Some(HistoryCursor {
+                block_index: block_index - 1,
+                position: u32::MAX,
+            }),
+            (block_index, position) => Some(HistoryCursor {
+                block_index,
+                position: position - 1,
+            }),
+        }
+    }
+}
+
+impl BytesSerialize for HistoryCursor {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buf = self.block_index.to_be_bytes().to_vec();
+        buf.extend(self.position.to_be_bytes());
+        buf
+    }
+}
+
+impl BytesDeserialize for HistoryCursor {
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, GulfStreamError> {
+        if buf.len() < 12 {
+            return Err(GulfStreamError::SerDeError("HistoryCursor".into()));
+        }
+        let (block_index, rest) = buf.split_at(8);
+        let (position, rest) = rest.split_at(4);
+        *buf = rest;
+        Ok(Self {
+            block_index: u64::from_be_bytes(block_index.try_into().unwrap()),
+            position: u32::from_be_bytes(position.try_into().unwrap()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryQuery {
+    pub address: Option<PublicKey>,
+    /// Lowest block index, included.
+    pub from_index: u64,
+    /// Highest block index, included.
+    pub to_index: u64,
+    /// Ignored without an address.
+    pub direction: Direction,
+    /// Last entry of the previous page.
+    pub cursor: Option<HistoryCursor>,
+    pub limit: usize,
+}
+
+impl Default for HistoryQuery {
+    fn default() -> Self {
+        Self {
+            address: None,
+            from_index: 0,
+            to_index: u64::MAX,
+            direction: Direction::Any,
+            cursor: None,
+            limit: Self::DEFAULT_LIMIT,
+        }
+    }
+}
+
+impl HistoryQuery {
+    pub const DEFAULT_LIMIT: usize = 50;
+    pub const MAX_LIMIT: usize = 500;
+
+    /// Position the page starts at, included. None if nothing is left to return.
+    pub fn get_upper_bound(&self) -> Option<HistoryCursor> {
+        let to = HistoryCursor {
+            block_index: self.to_index,
+            position: u32::MAX,
+        };
+        let upper_bound = match &self.cursor {
+            Some(cursor) => cursor.previous()?.min(to),
+            None => to,
+        };
+        if upper_bound.block_index < self.from_index {
+            None
+        } else {
+            Some(upper_bound)
+        }
+    }
+
+    pub fn get_lower_bound(&self) -> HistoryCursor {
+        HistoryCursor {
+            block_index: self.from_index,
+            position: 0,
+        }
+    }
+
+    pub fn get_limit(&self) -> usize {
+        self.limit.clamp(1, Self::MAX_LIMIT)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub cursor: HistoryCursor,
+    pub tx: Transaction,
+}
+
+impl HistoryEntry {
+    pub fn from_block(block: &Block) -> Vec<HistoryEntry> {
+        block
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(position, tx)| HistoryEntry {
+                cursor: HistoryCursor {
+                    block_index: block.index,
+                    position: position as u32,
+                },
+                tx: tx.clone(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    /// Set when more entries match the query.
+    pub next_cursor: Option<HistoryCursor>,
+}
+
+impl HistoryPage {
+    /// Takes a page out of `entries`, which go by decreasing position.
+    pub fn collect<I>(entries: I, limit: usize) -> Result<Self, GulfStreamError>
+    where
+        I: IntoIterator<Item = Result<HistoryEntry, GulfStreamError>>,
+    {
+        let mut entries = entries
+            .into_iter()
+            .take(limit + 1)
+            .collect::<Result<Vec<_>, _>>()?;
+        let next_cursor = if entries.len() > limit {
+            entries.truncate(limit);
+            entries.last().map(|entry| entry.cursor)
+        } else {
+            None
+        };
+        Ok(Self {
+            entries,
+            next_cursor,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::fixtures::unsigned;
+
+    #[test]
+    fn pages_through_cursor() {
+        let entries = |query: &HistoryQuery| {
+            let upper_bound = query.get_upper_bound();
+            (0..10u64)
+                .rev()
+                .map(|block_index| HistoryEntry {
+                    cursor: HistoryCursor {
+                        block_index,
+                        position: 0,
+                    },
+                    tx: unsigned(&PublicKey::default(), 0, 0),
+                })
+                .filter(move |entry| {
+                    matches!(upper_bound, Some(bound) if entry.cursor <= bound)
+                        && entry.cursor >= query.get_lower_bound()
+                })
+                .map(Ok)
+                .collect::<Vec<_>>()
+        };
+        let mut query = HistoryQuery {
+            from_index: 2,
+            to_index: 8,
+            limit: 4,
+            ..Default::default()
+        };
+
+        let page = HistoryPage::collect(entries(&query), query.get_limit()).unwrap();
+        let indexes: Vec<u64> = page.entries.iter().map(|e| e.cursor.block_index).collect();
+        assert_eq!(indexes, vec![8, 7, 6, 5]);
+
+        query.cursor = page.next_cursor;
+        let page = HistoryPage::collect(entries(&query), query.get_limit()).unwrap();
+        let indexes: Vec<u64> = page.entries.iter().map(|e| e.cursor.block_index).collect();
+        assert_eq!(indexes, vec![4, 3, 2]);
+        assert!(page.next_cursor.is_none());
+    }
+}
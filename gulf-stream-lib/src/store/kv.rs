@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use sled::transaction::ConflictableTransactionError;
+use sled::Transactional;
+
+use super::history::{HistoryCursor, HistoryEntry, HistoryPage, HistoryQuery, Involvement};
+use super::store::Store;
+use crate::ed25519::{publickey::PublicKey, signature::Signature};
+use crate::err::GulfStreamError;
+use crate::state::account::Account;
+use crate::state::block::Block;
+use crate::state::blockchain::ChainUpdate;
+use crate::state::blockhash::Blockhash;
+use crate::state::transaction::Transaction;
+use crate::utils::serde::{BytesDeserialize, BytesSerialize};
+
+/// Embedded on disk store, one sled tree per kind of record.
+pub struct KvStore {
+    db: sled::Db,
+    /// Keyed by big endian index then blockhash, so that iterating goes by increasing index.
+    blocks: sled::Tree,
+    txs: sled::Tree,
+    accounts: sled::Tree,
+    metadata: sled::Tree,
+    /// Canonical transactions keyed by position.
+    history: sled::Tree,
+    /// Keyed by public key then position, the value holds the sent and received flags.
+    address_history: sled::Tree,
+    /// Keyed by signature, the value holds the position then the blockhash.
+    positions: sled::Tree,
+}
+
+impl KvStore {
+    const HEAD_KEY: &[u8] = b"head";
+    const HISTORY_HEAD_KEY: &[u8] = b"history_head";
+
+    pub fn open(path: PathBuf) -> Result<Self, GulfStreamError> {
+        Self::from_db(sled::open(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn temporary() -> Result<Self, GulfStreamError> {
+        Self::from_db(sled::Config::new().temporary(true).open()?)
+    }
+
+    fn from_db(db: sled::Db) -> Result<Self, GulfStreamError> {
+        Ok(Self {
+            blocks: db.open_tree("blocks")?,
+            txs: db.open_tree("txs")?,
+            accounts: db.open_tree("accounts")?,
+            metadata: db.open_tree("metadata")?,
+            history: db.open_tree("history")?,
+            address_history: db.open_tree("address_history")?,
+            positions: db.open_tree("positions")?,
+            db,
+        })
+    }
+
+    fn block_key(block: &Block) -> Vec<u8> {
+        let mut key = block.index.to_be_bytes().to_vec();
+        key.extend(block.blockhash.as_ref());
+        key
+    }
+
+    fn address_history_key(pk: &PublicKey, cursor: &HistoryCursor) -> Vec<u8> {
+        let mut key = pk.serialize();
+        key.extend(cursor.serialize());
+        key
+    }
+
+    fn get_history_entry(&self, cursor: HistoryCursor) -> Result<HistoryEntry, GulfStreamError> {
+        let value = self
+            .history
+            .get(cursor.serialize())?
+            .ok_or(GulfStreamError::TxNotFound)?;
+        Ok(HistoryEntry {
+            cursor,
+            tx: Transaction::deserialize(&mut value.as_ref())?,
+        })
+    }
+
+    async fn flush(&self) -> Result<(), GulfStreamError> {
+        self.db.flush_async().await?;
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl Store for KvStore {
+    async fn insert_block(&self, block: &Block) -> Result<(), GulfStreamError> {
+        self.blocks
+            .insert(Self::block_key(block), block.serialize())?;
+        self.flush().await
+    }
+
+    async fn get_blocks(&self) -> Result<Vec<Block>, GulfStreamError> {
+        self.blocks
+            .iter()
+            .values()
+            .map(|value| Block::deserialize(&mut value?.as_ref()))
+            .collect()
+    }
+
+    async fn get_head(&self) -> Result<Option<Blockhash>, GulfStreamError> {
+        Ok(self
+            .metadata
+            .get(Self::HEAD_KEY)?
+            .map(|value| Blockhash(value.to_vec())))
+    }
+
+    async fn set_head(&self, blockhash: &Blockhash) -> Result<(), GulfStreamError> {
+        self.metadata.insert(Self::HEAD_KEY, blockhash.as_ref())?;
+        self.flush().await
+    }
+
+    async fn insert_tx(&self, tx: &Transaction) -> Result<(), GulfStreamError> {
+        self.txs.insert(tx.signature.serialize(), tx.serialize())?;
+        self.flush().await
+    }
+
+    async fn get_tx(&self, signature: &Signature) -> Result<Option<Transaction>, GulfStreamError> {
+        self.txs
+            .get(signature.serialize())?
+            .map(|value| Transaction::deserialize(&mut value.as_ref()))
+            .transpose()
+    }
+
+    async fn set_accounts(
+        &self,
+        accounts: &HashMap<PublicKey, Account>,
+    ) -> Result<(), GulfStreamError> {
+        let mut batch = sled::Batch::default();
+        accounts
+            .iter()
+            .for_each(|(pk, account)| batch.insert(pk.serialize(), account.serialize()));
+        self.accounts.apply_batch(batch)?;
+        self.flush().await
+    }
+
+    async fn get_account(&self, pk: &PublicKey) -> Result<Option<Account>, GulfStreamError> {
+        self.accounts
+            .get(pk.serialize())?
+            .map(|value| Account::deserialize(&mut value.as_ref()))
+            .transpose()
+    }
+
+    async fn index_history(
+        &self,
+        update: &ChainUpdate,
+        head: &Blockhash,
+    ) -> Result<(), GulfStreamError> {
+        let mut history = sled::Batch::default();
+        let mut address_history = sled::Batch::default();
+        let mut positions = sled::Batch::default();
+        for entry in update
+            .disconnected
+            .iter()
+            .flat_map(HistoryEntry::from_block)
+        {
+            history.remove(entry.cursor.serialize());
+            positions.remove(entry.tx.signature.serialize());
+            Involvement::from_tx(&entry.tx)
+                .iter()
+                .for_each(|involvement| {
+                    address_history
+                        .remove(Self::address_history_key(&involvement.pk, &entry.cursor));
+                });
+        }
+        for block in update.connected.iter() {
+            for entry in HistoryEntry::from_block(block) {
+                history.insert(entry.cursor.serialize(), entry.tx.serialize());
+                Involvement::from_tx(&entry.tx)
+                    .iter()
+                    .for_each(|involvement| {
+                        address_history.insert(
+                            Self::address_history_key(&involvement.pk, &entry.cursor),
+                            vec![involvement.sent as u8, involvement.received as u8],
+                        );
+                    });
+                let mut position = entry.cursor.serialize();
+                position.extend(block.blockhash.as_ref());
+                positions.insert(entry.tx.signature.serialize(), position);
+            }
+        }
+        // One transaction, so that the head marker never gets ahead of the index.
+        (
+            &self.history,
+            &self.address_history,
+            &self.positions,
+            &self.metadata,
+        )
+            .transaction(
+                |(history_tx, address_history_tx, positions_tx, metadata_tx)| {
+                    history_tx.apply_batch(&history)?;
+                    address_history_tx.apply_batch(&address_history)?;
+                    positions_tx.apply_batch(&positions)?;
+                    metadata_tx.insert(Self::HISTORY_HEAD_KEY, head.as_ref())?;
+                    Ok::<_, ConflictableTransactionError<sled::Error>>(())
+                },
+            )
+            .map_err(|err| GulfStreamError::map(err.to_string()))?;
+        self.flush().await
+    }
+
+    async fn get_history_head(&self) -> Result<Option<Blockhash>, GulfStreamError> {
+        Ok(self
+            .metadata
+            .get(Self::HISTORY_HEAD_KEY)?
+            .map(|value| Blockhash(value.to_vec())))
+    }
+
+    async fn clear_history(&self) -> Result<(), GulfStreamError> {
+        self.metadata.remove(Self::HISTORY_HEAD_KEY)?;
+        self.history.clear()?;
+        self.address_history.clear()?;
+        self.positions.clear()?;
+        self.flush().await
+    }
+
+    async fn get_history(&self, query: &HistoryQuery) -> Result<HistoryPage, GulfStreamError> {
+        let upper_bound = match query.get_upper_bound() {
+            Some(upper_bound) => upper_bound,
+            None => return Ok(HistoryPage::default()),
+        };
+        let lower_bound = query.get_lower_bound();
+        let cursors: Box<dyn Iterator<Item = Result<HistoryCursor, GulfStreamError>> + '_> =
+            match &query.address {
+                None => Box::new(
+                    self.history
+                        .range(lower_bound.serialize()..=upper_bound.serialize())
+                        .keys()
+                        .rev()
+                        .map(|key| HistoryCursor::deserialize(&mut key?.as_ref())),
+                ),
+                Some(pk) => {
+                    let prefix_len = pk.serialize().len();
+                    let direction = query.direction;
+                    Box::new(
+                        self.address_history
+                            .range(
+                                Self::address_history_key(pk, &lower_bound)
+                                    ..=Self::address_history_key(pk, &upper_bound),
+                            )
+                            .rev()
+                            .filter_map(move |item| {
+                                let (key, flags) = match item {
+                                    Ok(item) => item,
+                                    Err(err) => return Some(Err(err.into())),
+                                };
+                                let involvement = Involvement {
+                                    pk: pk.clone(),
+                                    sent: flags.first().copied().unwrap_or_default() != 0,
+                                    received: flags.get(1).copied().unwrap_or_default() != 0,
+                                };
+                                if direction.matches(&involvement) {
+                                    Some(HistoryCursor::deserialize(&mut &key[prefix_len..]))
+                                } else {
+                                    None
+                                }
+                            }),
+                    )
+                }
+            };
+        let entries = cursors.map(|cursor| self.get_history_entry(cursor?));
+        HistoryPage::collect(entries, query.get_limit())
+    }
+
+    async fn get_tx_in_history(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<(Blockhash, HistoryEntry)>, GulfStreamError> {
+        self.positions
+            .get(signature.serialize())?
+            .map(|value| {
+                let mut buf = value.as_ref();
+                let cursor = HistoryCursor::deserialize(&mut buf)?;
+                Ok((Blockhash(buf.to_vec()), self.get_history_entry(cursor)?))
+            })
+            .transpose()
+    }
+}
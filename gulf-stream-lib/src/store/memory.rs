@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, HashMap};
+
+use tokio::sync::Mutex;
+
+use super::history::{HistoryCursor, HistoryEntry, HistoryPage, HistoryQuery, Involvement};
+use super::store::Store;
+use crate::ed25519::{publickey::PublicKey, signature::Signature};
+use crate::err::GulfStreamError;
+use crate::state::account::Account;
+use crate::state::block::Block;
+use crate::state::blockchain::ChainUpdate;
+use crate::state::blockhash::Blockhash;
+use crate::state::transaction::Transaction;
+use crate::utils::serde::BytesSerialize;
+
+/// Store that keeps nothing across restarts, for tests and throwaway nodes.
+#[derive(Default)]
+pub struct MemoryStore {
+    blocks: Mutex<Vec<Block>>,
+    head: Mutex<Option<Blockhash>>,
+    txs: Mutex<HashMap<Signature, Transaction>>,
+    accounts: Mutex<HashMap<PublicKey, Account>>,
+    history: Mutex<BTreeMap<HistoryCursor, Transaction>>,
+    /// Keyed by serialized public key then position.
+    address_history: Mutex<BTreeMap<(Vec<u8>, HistoryCursor), Involvement>>,
+    positions: Mutex<HashMap<Signature, (Blockhash, HistoryCursor)>>,
+    history_head: Mutex<Option<Blockhash>>,
+}
+
+#[tonic::async_trait]
+impl Store for MemoryStore {
+    async fn insert_block(&self, block: &Block) -> Result<(), GulfStreamError> {
+        let mut blocks = self.blocks.lock().await;
+        if !blocks
+            .iter()
+            .any(|stored| stored.blockhash.eq(&block.blockhash))
+        {
+            blocks.push(block.clone());
+        }
+        Ok(())
+    }
+
+    async fn get_blocks(&self) -> Result<Vec<Block>, GulfStreamError> {
+        let mut blocks = self.blocks.lock().await.clone();
+        blocks.sort_by_key(|block| block.index);
+        Ok(blocks)
+    }
+
+    async fn get_head(&self) -> Result<Option<Blockhash>, GulfStreamError> {
+        Ok(self.head.lock().await.clone())
+    }
+
+    async fn set_head(&self, blockhash: &Blockhash) -> Result<(), GulfStreamError> {
+        *self.head.lock().await = Some(blockhash.clone());
+        Ok(())
+    }
+
+    async fn insert_tx(&self, tx: &Transaction) -> Result<(), GulfStreamError> {
+        self.txs
+            .lock()
+            .await
+            .entry(tx.signature.clone())
+            .or_insert_with(|| tx.clone());
+        Ok(())
+    }
+
+    async fn get_tx(&self, signature: &Signature) -> Result<Option<Transaction>, GulfStreamError> {
+        Ok(self.txs.lock().await.get(signature).cloned())
+    }
+
+    async fn set_accounts(
+        &self,
+        accounts: &HashMap<PublicKey, Account>,
+    ) -> Result<(), GulfStreamError> {
+        self.accounts
+            .lock()
+            .await
+            .extend(accounts.iter().map(|(pk, account)| (pk.clone(), *account)));
+        Ok(())
+    }
+
+    async fn get_account(&self, pk: &PublicKey) -> Result<Option<Account>, GulfStreamError> {
+        Ok(self.accounts.lock().await.get(pk).copied())
+    }
+
+    async fn index_history(
+        &self,
+        update: &ChainUpdate,
+        head: &Blockhash,
+    ) -> Result<(), GulfStreamError> {
+        let mut history = self.history.lock().await;
+        let mut address_history = self.address_history.lock().await;
+        let mut positions = self.positions.lock().await;
+        for entry in update
+            .disconnected
+            .iter()
+            .flat_map(HistoryEntry::from_block)
+        {
+            history.remove(&entry.cursor);
+            positions.remove(&entry.tx.signature);
+            Involvement::from_tx(&entry.tx)
+                .iter()
+                .for_each(|involvement| {
+                    address_history.remove(&(involvement.pk.serialize(), entry.cursor));
+                });
+        }
+        for block in update.connected.iter() {
+            for entry in HistoryEntry::from_block(block) {
+                Involvement::from_tx(&entry.tx)
+                    .into_iter()
+                    .for_each(|involvement| {
+                        address_history
+                            .insert((involvement.pk.serialize(), entry.cursor), involvement);
+                    });
+                positions.insert(
+                    entry.tx.signature.clone(),
+                    (block.blockhash.clone(), entry.cursor),
+                );
+                history.insert(entry.cursor, entry.tx);
+            }
+        }
+        *self.history_head.lock().await = Some(head.clone());
+        Ok(())
+    }
+
+    async fn get_history_head(&self) -> Result<Option<Blockhash>, GulfStreamError> {
+        Ok(self.history_head.lock().await.clone())
+    }
+
+    async fn clear_history(&self) -> Result<(), GulfStreamError> {
+        self.history.lock().await.clear();
+        self.address_history.lock().await.clear();
+        self.positions.lock().await.clear();
+        *self.history_head.lock().await = None;
+        Ok(())
+    }
+
+    async fn get_history(&self, query: &HistoryQuery) -> Result<HistoryPage, GulfStreamError> {
+        let upper_bound = match query.get_upper_bound() {
+            Some(upper_bound) => upper_bound,
+            None => return Ok(HistoryPage::default()),
+        };
+        let lower_bound = query.get_lower_bound();
+        let history = self.history.lock().await;
+        let cursors: Box<dyn Iterator<Item = HistoryCursor> + '_> = match &query.address {
+            None => Box::new(
+                history
+                    .range(lower_bound..=upper_bound)
+                    .rev()
+                    .map(|(cursor, _)| *cursor),
+            ),
+            Some(pk) => {
+                let address_history = self.address_history.lock().await;
+                let key = pk.serialize();
+                let cursors: Vec<HistoryCursor> = address_history
+                    .range((key.clone(), lower_bound)..=(key, upper_bound))
+                    .rev()
+                    .filter(|(_, involvement)| query.direction.matches(involvement))
+                    .map(|((_, cursor), _)| *cursor)
+                    .take(query.get_limit() + 1)
+                    .collect();
+                Box::new(cursors.into_iter())
+            }
+        };
+        let entries = cursors.map(|cursor| {
+            history
+                .get(&cursor)
+                .map(|tx| HistoryEntry {
+                    cursor,
+                    tx: tx.clone(),
+                })
+                .ok_or(GulfStreamError::TxNotFound)
+        });
+        HistoryPage::collect(entries, query.get_limit())
+    }
+
+    async fn get_tx_in_history(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<(Blockhash, HistoryEntry)>, GulfStreamError> {
+        let (blockhash, cursor) = match self.positions.lock().await.get(signature).cloned() {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+        let tx = self
+            .history
+            .lock()
+            .await
+            .get(&cursor)
+            .cloned()
+            .ok_or(GulfStreamError::TxNotFound)?;
+        Ok(Some((blockhash, HistoryEntry { cursor, tx })))
+    }
+}
@@ -0,0 +1,64 @@
+/// Step of the postgres schema. Migrations run once each, by increasing version, and the
+/// versions already applied are kept in the `schema_version` table.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub statements: &'static [&'static str],
+}
+
+pub const SCHEMA_VERSION_TABLE: &str = "CREATE TABLE IF NOT EXISTS schema_version ( version bigint PRIMARY KEY, name varchar(64) NOT NULL, applied_at timestamptz NOT NULL DEFAULT now() );";
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        // Tables created before the schema was versioned have a shape that depends on the
+        // node release, keep them aside rather than guess it.
+        name: "set_aside_unversioned_tables",
+        statements: &[
+            "ALTER TABLE IF EXISTS blocks RENAME TO unversioned_blocks;",
+            "ALTER TABLE IF EXISTS txs RENAME TO unversioned_txs;",
+            "ALTER TABLE IF EXISTS accounts RENAME TO unversioned_accounts;",
+            "ALTER TABLE IF EXISTS chain_metadata RENAME TO unversioned_chain_metadata;",
+        ],
+    },
+    Migration {
+        version: 2,
+        name: "create_chain_tables",
+        statements: &[
+            "CREATE TABLE blocks ( blockhash bytea PRIMARY KEY, blockheight bigint NOT NULL, previous_blockhash bytea NOT NULL, nonce bigint NOT NULL, difficulty integer NOT NULL, timestamp bigint NOT NULL );",
+            "CREATE INDEX blocks_blockheight ON blocks (blockheight);",
+            "CREATE TABLE transactions ( signature varchar(92) PRIMARY KEY, valid_from bigint NOT NULL, valid_until bigint NOT NULL, gas bigint NOT NULL, nonce bigint NOT NULL, payer varchar(44) NOT NULL, msg_kind smallint NOT NULL, msg_to varchar(44), msg_amount bigint NOT NULL );",
+            "CREATE INDEX transactions_payer ON transactions (payer, nonce);",
+            "CREATE TABLE block_transactions ( blockhash bytea NOT NULL REFERENCES blocks (blockhash), position integer NOT NULL, signature varchar(92) NOT NULL REFERENCES transactions (signature), PRIMARY KEY (blockhash, position) );",
+            "CREATE INDEX block_transactions_signature ON block_transactions (signature);",
+            "CREATE TABLE accounts ( publickey varchar(44) PRIMARY KEY, balance bigint NOT NULL, nonce bigint NOT NULL );",
+            "CREATE TABLE chain_metadata ( key varchar(32) PRIMARY KEY, value bytea NOT NULL );",
+        ],
+    },
+    Migration {
+        version: 3,
+        // Which blocks are canonical is only known to the node, it fills the flag back when
+        // it finds the history head behind its own.
+        name: "index_transaction_history",
+        statements: &[
+            "ALTER TABLE blocks ADD COLUMN canonical boolean NOT NULL DEFAULT false;",
+            "CREATE INDEX blocks_canonical ON blocks (blockheight) WHERE canonical;",
+            "CREATE TABLE address_transactions ( address varchar(44) NOT NULL, signature varchar(92) NOT NULL REFERENCES transactions (signature), sent boolean NOT NULL, received boolean NOT NULL, PRIMARY KEY (address, signature) );",
+            "INSERT INTO address_transactions (address,signature,sent,received) SELECT payer, signature, true, msg_kind = 0 FROM transactions;",
+            "INSERT INTO address_transactions (address,signature,sent,received) SELECT msg_to, signature, false, true FROM transactions WHERE msg_kind = 1 ON CONFLICT (address, signature) DO UPDATE SET received = true;",
+        ],
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn versions_are_consecutive() {
+        MIGRATIONS
+            .iter()
+            .enumerate()
+            .for_each(|(i, migration)| assert_eq!(migration.version, i as i64 + 1));
+    }
+}
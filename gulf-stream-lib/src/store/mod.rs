@@ -1 +1,6 @@
 pub mod db;
+pub mod history;
+pub mod kv;
+pub mod memory;
+pub mod migrations;
+pub mod store;
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use super::history::{HistoryEntry, HistoryPage, HistoryQuery};
+use crate::ed25519::{publickey::PublicKey, signature::Signature};
+use crate::err::GulfStreamError;
+use crate::state::account::Account;
+use crate::state::block::Block;
+use crate::state::blockchain::{Blockchain, ChainUpdate};
+use crate::state::blockhash::Blockhash;
+use crate::state::genesis::Genesis;
+use crate::state::transaction::Transaction;
+
+/// Persistence of the node : every block it accepted, the canonical head, the transactions
+/// it was sent and the account state at the head.
+#[tonic::async_trait]
+pub trait Store: Send + Sync {
+    /// Inserting a block already stored is a no-op.
+    async fn insert_block(&self, block: &Block) -> Result<(), GulfStreamError>;
+
+    /// Every stored block, forks included, by increasing index.
+    async fn get_blocks(&self) -> Result<Vec<Block>, GulfStreamError>;
+
+    /// Blockhash of the canonical head when the node last switched head.
+    async fn get_head(&self) -> Result<Option<Blockhash>, GulfStreamError>;
+
+    async fn set_head(&self, blockhash: &Blockhash) -> Result<(), GulfStreamError>;
+
+    /// Inserting a transaction already stored is a no-op.
+    async fn insert_tx(&self, tx: &Transaction) -> Result<(), GulfStreamError>;
+
+    async fn get_tx(&self, signature: &Signature) -> Result<Option<Transaction>, GulfStreamError>;
+
+    /// Overwrites the given accounts of the canonical state.
+    async fn set_accounts(
+        &self,
+        accounts: &HashMap<PublicKey, Account>,
+    ) -> Result<(), GulfStreamError>;
+
+    async fn get_account(&self, pk: &PublicKey) -> Result<Option<Account>, GulfStreamError>;
+
+    /// Moves the transaction history index along the canonical chain, up to `head`.
+    async fn index_history(
+        &self,
+        update: &ChainUpdate,
+        head: &Blockhash,
+    ) -> Result<(), GulfStreamError>;
+
+    /// Head the transaction history index was last moved to.
+    async fn get_history_head(&self) -> Result<Option<Blockhash>, GulfStreamError>;
+
+    async fn clear_history(&self) -> Result<(), GulfStreamError>;
+
+    /// Canonical transactions matching `query`, latest first.
+    async fn get_history(&self, query: &HistoryQuery) -> Result<HistoryPage, GulfStreamError>;
+
+    /// A canonical transaction with the blockhash and position it has in the history index.
+    async fn get_tx_in_history(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<(Blockhash, HistoryEntry)>, GulfStreamError>;
+
+    /// Rebuilds the chain, with its account state, from the stored blocks. The history
+    /// index is rebuilt too if it does not follow the restored head.
+    async fn load_blockchain(&self, genesis: &Genesis) -> Result<Blockchain, GulfStreamError> {
+        let blocks = self.get_blocks().await?;
+        let head = self.get_head().await?;
+        let blockchain = Blockchain::restore(genesis, blocks, head.as_ref())?;
+        let latest_block = blockchain.get_latest().block.clone();
+        if self.get_history_head().await?.as_ref() != Some(&latest_block.blockhash) {
+            let update = ChainUpdate {
+                connected: blockchain.get_blocks(0, latest_block.index + 1),
+                disconnected: vec![],
+            };
+            self.clear_history().await?;
+            self.index_history(&update, &latest_block.blockhash).await?;
+        }
+        Ok(blockchain)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        store::{
+            history::{Direction, HistoryCursor},
+            kv::KvStore,
+            memory::MemoryStore,
+        },
+        utils::fixtures::*,
+    };
+
+    async fn round_trip(store: &dyn Store) {
+        let signer = authority();
+        let genesis = genesis();
+        let tx = mint(&signer, 1, 0, 10);
+        let block1 = mine(&genesis.get_block(), vec![tx.clone()], 0);
+        let block2 = mine(&block1, vec![], 0);
+
+        assert!(store.get_head().await.unwrap().is_none());
+        assert!(
+            store
+                .load_blockchain(&genesis)
+                .await
+                .unwrap()
+                .get_latest()
+                .block
+                == genesis.get_block()
+        );
+
+        store.insert_block(&block2).await.unwrap();
+        store.insert_block(&block1).await.unwrap();
+        store.insert_block(&block1).await.unwrap();
+        store.set_head(&block2.blockhash).await.unwrap();
+        assert_eq!(
+            store.get_blocks().await.unwrap(),
+            vec![block1, block2.clone()]
+        );
+        assert_eq!(
+            store.get_head().await.unwrap(),
+            Some(block2.blockhash.clone())
+        );
+        let blockchain = store.load_blockchain(&genesis).await.unwrap();
+        assert_eq!(blockchain.get_latest().block, block2);
+
+        store.insert_tx(&tx).await.unwrap();
+        store.insert_tx(&tx).await.unwrap();
+        assert_eq!(store.get_tx(&tx.signature).await.unwrap(), Some(tx.clone()));
+        let unknown_tx = mint(&signer, 2, 1, 10);
+        assert!(store.get_tx(&unknown_tx.signature).await.unwrap().is_none());
+
+        let accounts = blockchain.get_latest().get_accounts();
+        store.set_accounts(&accounts).await.unwrap();
+        assert_eq!(
+            store.get_account(&tx.payer).await.unwrap(),
+            accounts.get(&tx.payer).copied()
+        );
+        assert!(store
+            .get_account(&PublicKey::random())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    async fn history(store: &dyn Store) {
+        let (alice, bob) = (authority(), keypair());
+        let minted = mint(&alice, 1, 0, 10);
+        let pay = |nonce, valid_from| transfer(&alice, valid_from, nonce, &bob, 1);
+        let block1 = mine(&genesis().get_block(), vec![minted.clone()], 0);
+        let block2 = mine(&block1, vec![pay(1, 2), pay(2, 2)], 0);
+        let fork2 = mine(&block1, vec![pay(1, 2)], 0);
+        let fork3 = mine(&fork2, vec![pay(2, 3)], 0);
+        let update = ChainUpdate {
+            connected: vec![block1.clone(), block2.clone()],
+            disconnected: vec![],
+        };
+        store
+            .index_history(&update, &block2.blockhash)
+            .await
+            .unwrap();
+        let reorg = ChainUpdate {
+            connected: vec![fork2.clone(), fork3.clone()],
+            disconnected: vec![block2.clone()],
+        };
+        store.index_history(&reorg, &fork3.blockhash).await.unwrap();
+        assert_eq!(
+            store.get_history_head().await.unwrap(),
+            Some(fork3.blockhash.clone())
+        );
+        let (blockhash, entry) = store
+            .get_tx_in_history(&fork3.transactions[1].signature)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(blockhash, fork3.blockhash);
+        assert_eq!(
+            entry.cursor,
+            HistoryCursor {
+                block_index: 3,
+                position: 1
+            }
+        );
+        assert!(store
+            .get_tx_in_history(&block2.transactions[2].signature)
+            .await
+            .unwrap()
+            .is_none());
+
+        let query = HistoryQuery {
+            limit: 3,
+            ..Default::default()
+        };
+        let page = store.get_history(&query).await.unwrap();
+        let txs: Vec<Transaction> = page.entries.into_iter().map(|entry| entry.tx).collect();
+        assert_eq!(
+            txs,
+            vec![
+                fork3.transactions[1].clone(),
+                fork3.transactions[0].clone(),
+                fork2.transactions[1].clone()
+            ]
+        );
+        assert_eq!(
+            page.next_cursor,
+            Some(HistoryCursor {
+                block_index: 2,
+                position: 1
+            })
+        );
+        let query = HistoryQuery {
+            cursor: page.next_cursor,
+            ..query
+        };
+        let page = store.get_history(&query).await.unwrap();
+        let txs: Vec<Transaction> = page.entries.into_iter().map(|entry| entry.tx).collect();
+        assert_eq!(
+            txs,
+            vec![
+                fork2.transactions[0].clone(),
+                minted,
+                block1.transactions[0].clone()
+            ]
+        );
+        assert!(page.next_cursor.is_none());
+
+        let received = HistoryQuery {
+            address: Some(PublicKey(bob.public)),
+            direction: Direction::Received,
+            to_index: 2,
+            ..Default::default()
+        };
+        let page = store.get_history(&received).await.unwrap();
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].tx, fork2.transactions[1]);
+        let sent = HistoryQuery {
+            direction: Direction::Sent,
+            ..received
+        };
+        assert!(store.get_history(&sent).await.unwrap().entries.is_empty());
+
+        store.clear_history().await.unwrap();
+        assert!(store.get_history_head().await.unwrap().is_none());
+        assert!(store
+            .get_history(&HistoryQuery::default())
+            .await
+            .unwrap()
+            .entries
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn memory_round_trip() {
+        round_trip(&MemoryStore::default()).await;
+        history(&MemoryStore::default()).await;
+    }
+
+    #[tokio::test]
+    async fn kv_round_trip() {
+        round_trip(&KvStore::temporary().unwrap()).await;
+        history(&KvStore::temporary().unwrap()).await;
+    }
+}
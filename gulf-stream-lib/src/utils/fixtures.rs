@@ -0,0 +1,140 @@
+use ed25519_dalek::Keypair;
+use rand::rngs::OsRng;
+
+use crate::ed25519::{publickey::PublicKey, signature::Signature};
+use crate::state::{
+    amount::Amount,
+    block::Block,
+    difficulty::TARGET_BLOCK_TIME,
+    genesis::Genesis,
+    policy::MonetaryPolicy,
+    transaction::{Transaction, TransactionMessage},
+};
+
+pub fn keypair() -> Keypair {
+    Keypair::generate(&mut OsRng {})
+}
+
+fn fixed_keypair(byte: u8) -> Keypair {
+    let secret = ed25519_dalek::SecretKey::from_bytes(&[byte; 32]).unwrap();
+    let public = (&secret).into();
+    Keypair { secret, public }
+}
+
+/// Same key in every test, the only one allowed to mint.
+pub fn authority() -> Keypair {
+    fixed_keypair(7)
+}
+
+/// Same key in every test, the one blocks are paid to.
+pub fn producer() -> Keypair {
+    fixed_keypair(9)
+}
+
+pub fn genesis() -> Genesis {
+    Genesis {
+        policy: MonetaryPolicy {
+            mint_authorities: vec![PublicKey(authority().public)],
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+pub fn policy() -> MonetaryPolicy {
+    genesis().policy
+}
+
+/// Signed for the default chain, valid over the widest window from `valid_from`.
+pub fn sign(
+    signer: &Keypair,
+    valid_from: u64,
+    gas: u64,
+    nonce: u64,
+    msg: TransactionMessage,
+) -> Transaction {
+    let valid_until = valid_from + Transaction::MAX_VALIDITY_WINDOW;
+    Signature::sign_payload(
+        signer,
+        Genesis::DEFAULT_CHAIN_ID,
+        valid_from,
+        valid_until,
+        gas,
+        nonce,
+        msg,
+    )
+}
+
+pub fn mint(signer: &Keypair, valid_from: u64, nonce: u64, amount: u64) -> Transaction {
+    let msg = TransactionMessage::Mint {
+        amount: Amount(amount),
+    };
+    sign(signer, valid_from, 0, nonce, msg)
+}
+
+pub fn transfer(
+    signer: &Keypair,
+    valid_from: u64,
+    nonce: u64,
+    to: &Keypair,
+    amount: u64,
+) -> Transaction {
+    let msg = TransactionMessage::Transfer {
+        to: PublicKey(to.public),
+        amount: Amount(amount),
+    };
+    sign(signer, valid_from, 0, nonce, msg)
+}
+
+/// Mint valid from block 1 whose payer offers `gas`.
+pub fn paying(signer: &Keypair, nonce: u64, gas: u64) -> Transaction {
+    sign(signer, 1, gas, nonce, TransactionMessage::default())
+}
+
+/// Mint without a valid signature, for code that never checks it.
+pub fn unsigned(payer: &PublicKey, nonce: u64, amount: u64) -> Transaction {
+    Transaction {
+        valid_from: 0,
+        valid_until: 0,
+        gas: 0,
+        nonce,
+        msg: TransactionMessage::Mint {
+            amount: Amount(amount),
+        },
+        payer: payer.clone(),
+        signature: Default::default(),
+    }
+}
+
+/// Pays the block at `index` to `producer()`, unless the transactions bring their own
+/// coinbase.
+pub fn with_coinbase(index: u64, transactions: Vec<Transaction>) -> Vec<Transaction> {
+    if matches!(transactions.first(), Some(tx) if tx.is_coinbase()) {
+        return transactions;
+    }
+    let fees = Block::get_fees_from_txs(&transactions).unwrap();
+    let coinbase = policy()
+        .create_coinbase(&producer(), Genesis::DEFAULT_CHAIN_ID, index, fees)
+        .unwrap();
+    std::iter::once(coinbase).chain(transactions).collect()
+}
+
+pub fn child(parent: &Block, transactions: Vec<Transaction>, nonce: u64) -> Block {
+    Block::create_block(
+        parent.index + 1,
+        &parent.blockhash,
+        with_coinbase(parent.index + 1, transactions),
+        nonce,
+        parent.difficulty,
+        parent.timestamp + TARGET_BLOCK_TIME,
+    )
+}
+
+/// Mines a child of `parent`, starting the nonce search at `first_nonce` so that two
+/// siblings with the same transactions still differ.
+pub fn mine(parent: &Block, transactions: Vec<Transaction>, first_nonce: u64) -> Block {
+    (first_nonce..)
+        .map(|nonce| child(parent, transactions.clone(), nonce))
+        .find(|block| block.blockhash.is_valid(block.difficulty))
+        .unwrap()
+}
@@ -1 +1,5 @@
+#[cfg(test)]
+pub mod fixtures;
+pub mod seen;
 pub mod serde;
+pub mod time;
@@ -0,0 +1,55 @@
+use std::collections::{HashSet, VecDeque};
+use std::hash::Hash;
+
+/// Remembers the last `capacity` items it saw, the oldest is forgotten first.
+pub struct SeenSet<T> {
+    capacity: usize,
+    items: HashSet<T>,
+    arrival: VecDeque<T>,
+}
+
+impl<T: Hash + Eq + Clone> SeenSet<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            items: HashSet::new(),
+            arrival: VecDeque::new(),
+        }
+    }
+
+    pub fn contains(&self, item: &T) -> bool {
+        self.items.contains(item)
+    }
+
+    /// Returns false if `item` was already seen.
+    pub fn insert(&mut self, item: T) -> bool {
+        if self.capacity == 0 || self.contains(&item) {
+            return false;
+        }
+        if self.arrival.len() >= self.capacity {
+            if let Some(oldest) = self.arrival.pop_front() {
+                self.items.remove(&oldest);
+            }
+        }
+        self.items.insert(item.clone());
+        self.arrival.push_back(item);
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn forgets_oldest() {
+        let mut seen = SeenSet::new(2);
+        assert!(seen.insert(1));
+        assert!(!seen.insert(1));
+        assert!(seen.insert(2));
+        assert!(seen.insert(3));
+        assert!(!seen.contains(&1));
+        assert!(seen.contains(&2) && seen.contains(&3));
+        assert!(seen.insert(1));
+    }
+}
@@ -30,6 +30,53 @@ impl BytesSerialize for u64 {
     }
 }
 
+impl BytesDeserialize for u32 {
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, GulfStreamError>
+    where
+        Self: Sized,
+    {
+        if buf.len() < 4 {
+            return Err(GulfStreamError::SerDeError("u32".into()));
+        }
+        let data = &buf[0..4];
+        *buf = &buf[4..];
+        return Ok(u32::from_le_bytes(
+            data.try_into()
+                .map_err(|_| GulfStreamError::SerDeError("u32".into()))?,
+        ));
+    }
+}
+
+impl BytesSerialize for u32 {
+    fn serialize(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+/// Length prefixed bytes.
+impl BytesDeserialize for Vec<u8> {
+    fn deserialize(buf: &mut &[u8]) -> Result<Self, GulfStreamError>
+    where
+        Self: Sized,
+    {
+        let len = u64::deserialize(buf)? as usize;
+        if buf.len() < len {
+            return Err(GulfStreamError::SerDeError("bytes".into()));
+        }
+        let data = buf[..len].to_vec();
+        *buf = &buf[len..];
+        Ok(data)
+    }
+}
+
+impl BytesSerialize for Vec<u8> {
+    fn serialize(&self) -> Vec<u8> {
+        let mut vec = (self.len() as u64).serialize();
+        vec.extend(self);
+        vec
+    }
+}
+
 impl BytesDeserialize for bool {
     fn deserialize(buf: &mut &[u8]) -> Result<Self, GulfStreamError>
     where
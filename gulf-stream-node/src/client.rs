@@ -1,41 +1,61 @@
 use gulf_stream_lib::{
-    ed25519::{publickey::PublicKey, signature::Signature},
-    pb::{node_client::NodeClient, SendTransactionRequest},
-    state::transaction::TransactionMessage,
+    ed25519::{keypair::keypair_from_str, publickey::PublicKey, signature::Signature},
+    pb::{node_client::NodeClient, GetBalanceRequest, HandshakeRequest, SendTransactionRequest},
+    state::{
+        amount::Amount,
+        transaction::{Transaction, TransactionMessage},
+    },
 };
 
 use anyhow::Result;
+use clap::Parser;
 use ed25519_dalek::Keypair;
 use rand::rngs::OsRng;
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let mut client = NodeClient::connect("http://0.0.0.0:50051").await?;
-    let mut csprng = OsRng {};
-    let keypair: Keypair = Keypair::generate(&mut csprng);
+const VALID_UNTIL: u64 = Transaction::MAX_VALIDITY_WINDOW;
 
-    let msg = TransactionMessage::default();
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(long, default_value = "http://0.0.0.0:50051")]
+    node: String,
 
-    let request = tonic::Request::new(SendTransactionRequest {
-        tx: Some(
-            Signature::sign_payload(&keypair, 1, 5, msg)
-                .try_into()
-                .unwrap(),
-        ),
-    });
-
-    let response = client.send_transaction(request).await?;
+    /// Base58 secret key of a mint authority of the node.
+    #[arg(long)]
+    key: String,
+}
 
-    println!("RESPONSE={:?}", response);
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut client = NodeClient::connect(args.node).await?;
+    let mut csprng = OsRng {};
+    let keypair = keypair_from_str(&args.key)?;
+
+    // Transactions are only valid on the network they are signed for.
+    let chain_id = client
+        .handshake(HandshakeRequest::default())
+        .await?
+        .into_inner()
+        .chain_id;
+
+    let nonce = client
+        .get_balance(GetBalanceRequest {
+            address: keypair.public.to_bytes().to_vec(),
+        })
+        .await?
+        .into_inner()
+        .nonce;
 
     let msg = TransactionMessage::default();
 
     let request = tonic::Request::new(SendTransactionRequest {
         tx: Some(
-            Signature::sign_payload(&keypair, 2, 10, msg)
+            Signature::sign_payload(&keypair, &chain_id, 0, VALID_UNTIL, 5, nonce, msg)
                 .try_into()
                 .unwrap(),
         ),
+        ..Default::default()
     });
 
     let response = client.send_transaction(request).await?;
@@ -46,15 +66,16 @@ async fn main() -> Result<()> {
 
     let msg = TransactionMessage::Transfer {
         to: PublicKey(keypair2.public),
-        amount: 17,
+        amount: Amount(17),
     };
 
     let request = tonic::Request::new(SendTransactionRequest {
         tx: Some(
-            Signature::sign_payload(&keypair, 3, 2, msg)
+            Signature::sign_payload(&keypair, &chain_id, 0, VALID_UNTIL, 2, nonce + 1, msg)
                 .try_into()
                 .unwrap(),
         ),
+        ..Default::default()
     });
 
     let response = client.send_transaction(request).await?;
@@ -1,14 +1,18 @@
 use std::{path::PathBuf, sync::Arc};
 
 use gulf_stream_lib::{
+    ed25519::{keypair::keypair_from_str, publickey::PublicKey},
     ledger::ledger::*,
-    state::{blockchain::Blockchain, transaction::Transaction},
-    store::db::DbClient,
+    mempool::mempool::Mempool,
+    peer::{metrics::DeliveryMetrics, peers::PeerBook, pool::ConnectionPool},
+    state::genesis::Genesis,
+    store::{db::DbClient, kv::KvStore, memory::MemoryStore, store::Store},
+    utils::{seen::SeenSet, time::unix_timestamp},
 };
 use tokio::sync::Mutex;
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -16,70 +20,134 @@ struct Args {
     #[arg(short, long)]
     port: u64,
 
+    /// Port of a node on this host to bootstrap from.
     #[arg(long)]
     host_known: Option<u64>,
 
+    /// Comma separated `host:port` list of nodes to bootstrap from.
+    #[arg(long, value_delimiter = ',')]
+    peers: Vec<String>,
+
+    #[arg(long, default_value_t = PeerBook::DEFAULT_MAX_PEERS)]
+    max_peers: usize,
+
+    /// Host other nodes reach this node at.
+    #[arg(long, default_value = "0.0.0.0")]
+    host: String,
+
     #[arg(short, long, default_value_t = false)]
     reset: bool,
+
+    #[arg(long, value_enum, default_value_t = StoreKind::Postgres)]
+    store: StoreKind,
+
+    /// Base58 secret key the block rewards and fees are paid to. The node builds no blocks without it.
+    #[arg(long)]
+    miner_key: Option<String>,
+
+    /// JSON file describing the network to join, the default network if none.
+    #[arg(long)]
+    genesis: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum StoreKind {
+    /// Embedded postgres, fetched on first start.
+    Postgres,
+    /// Embedded key-value database under ./data/kv.
+    Kv,
+    /// Nothing is kept across restarts.
+    Memory,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
-    let addr = format!("0.0.0.0:{}", args.port).parse()?;
-
-    let other_nodes = if let Some(host_known) = args.host_known {
-        let host_known = format!("http://0.0.0.0:{}", host_known);
-        Mutex::new(vec![host_known.try_into().unwrap()])
-    } else {
-        Mutex::new(vec![])
+    let genesis = match &args.genesis {
+        Some(path) => Genesis::load(path)?,
+        None => Genesis::default(),
     };
-
-    if args.reset {
-        std::fs::remove_dir_all(PathBuf::from("./data"))?;
+    println!(
+        "Chain {} with genesis {}",
+        genesis.chain_id,
+        genesis.get_block().blockhash
+    );
+    let miner = args
+        .miner_key
+        .as_deref()
+        .map(keypair_from_str)
+        .transpose()?;
+    if let Some(miner) = &miner {
+        println!(
+            "Block rewards go to {}",
+            PublicKey(miner.public).into_string()
+        );
     }
 
-    let pg_runtime = DbClient::launch_pg_embed().await?;
+    let addr = format!("0.0.0.0:{}", args.port).parse()?;
+
+    let endpoint = PeerBook::parse_endpoint(&format!("{}:{}", args.host, args.port))?;
 
-    if args.reset {
-        pg_runtime.create_database("state").await?;
+    let mut peers = PeerBook::new(args.max_peers, Some(&endpoint));
+    let bootstrap = args
+        .host_known
+        .map(|host_known| format!("0.0.0.0:{}", host_known))
+        .into_iter()
+        .chain(args.peers.clone());
+    for address in bootstrap {
+        peers.add(PeerBook::parse_endpoint(&address)?, unix_timestamp());
     }
 
-    let client = DbClient::new(pg_runtime.full_db_uri("state")).await?;
+    if args.reset && PathBuf::from("./data").exists() {
+        std::fs::remove_dir_all(PathBuf::from("./data"))?;
+    }
 
-    println!("DB uri {}", pg_runtime.full_db_uri("state"));
+    // The embedded postgres stops when its handle is dropped, so it lives as long as main.
+    let pg_runtime = match args.store {
+        StoreKind::Postgres => Some(DbClient::launch_pg_embed().await?),
+        _ => None,
+    };
 
-    if args.reset {
-        client.init_tables().await?;
-    }
+    let store: Arc<dyn Store> = match args.store {
+        StoreKind::Postgres => {
+            let pg_runtime = pg_runtime.as_ref().unwrap();
+            if args.reset {
+                pg_runtime.create_database("state").await?;
+            }
+            let client = DbClient::new(pg_runtime.full_db_uri("state")).await?;
+            println!("DB uri {}", pg_runtime.full_db_uri("state"));
+            client.migrate().await?;
+            Arc::new(client)
+        }
+        StoreKind::Kv => Arc::new(KvStore::open(PathBuf::from("data/kv"))?),
+        StoreKind::Memory => Arc::new(MemoryStore::default()),
+    };
 
-    client
-        .insert_tx(&Transaction {
-            blockheight: 5,
-            gas: 62,
-            msg: Default::default(),
-            payer: Default::default(),
-            signature: Default::default(),
-        })
-        .await?;
+    let state = store.load_blockchain(&genesis).await?;
 
     let ledger = Arc::new(Ledger {
-        state: Mutex::new(Blockchain::default()),
-        other_nodes,
-        mem_pool: Mutex::new(vec![]),
-        db: Arc::new(client),
+        state: Mutex::new(state),
+        peers: Mutex::new(peers),
+        mem_pool: Mutex::new(Mempool::default()),
+        store,
+        endpoint: Some(endpoint),
+        seen_txs: Mutex::new(SeenSet::new(Ledger::SEEN_TXS_CAPACITY)),
+        connections: ConnectionPool::default(),
+        delivery: DeliveryMetrics::default(),
+        events: Ledger::new_events(),
+        miner,
     });
 
     let rpc_runtime = ledger.clone().run_rpc(addr);
     let node_runtime = ledger.clone().run_node();
     let logs = ledger.clone().run_logs();
-    let db_runtime = ledger.clone().run_db();
+    let peers_runtime = ledger.clone().run_peers();
 
     rpc_runtime.await??;
     logs.await?;
+    peers_runtime.await?;
     node_runtime.await??;
-    db_runtime.await??;
 
     Ok(())
 }